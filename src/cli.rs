@@ -0,0 +1,120 @@
+use crate::shell::options::ShellOptions;
+use crate::shell::scripting::lint::LintFormat;
+
+pub const USAGE: &str = "\
+Usage: 0-shell [option ...] [script [arg ...]]
+       0-shell [option ...] -c command [name [arg ...]]
+       0-shell [option ...] -s [arg ...]
+
+Options:
+  -c command    run command, then exit
+  -s            read commands from standard input
+  -i            force an interactive shell
+  -l, --login   act as a login shell (reads ~/.profile)
+  -o option     enable a shell option (+o option disables it)
+  -e, -u, ...   single-letter shell options (+e, +u, ... disable them)
+  -n            read and check commands without running them
+  --lint[=FMT]  report common mistakes in the script (FMT: human, json)
+  --version     print version information and exit
+  --help        print this help and exit";
+
+/// Where the commands come from.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Source {
+    /// `-c STRING`
+    Command(String),
+    /// `0-shell FILE`
+    Script(String),
+    /// `-s`, or no script with stdin not a terminal.
+    Stdin,
+    /// No script: interactive if stdin is a terminal (or `-i`), else stdin.
+    Default,
+}
+
+#[derive(Debug)]
+pub struct Invocation {
+    pub source: Source,
+    /// `$0` override (`-c cmd NAME`).
+    pub arg0: Option<String>,
+    pub positional: Vec<String>,
+    pub interactive: bool,
+    pub login: bool,
+    /// `-o name` / `+o name` / `-e` / `+e`, in order.
+    pub options: Vec<(String, bool)>,
+    /// `--lint`: check the script instead of running it.
+    pub lint: Option<LintFormat>,
+}
+
+pub enum Action {
+    Run(Invocation),
+    Version,
+    Help,
+}
+
+/// Parse `argv[1..]`.
+pub fn parse(args: &[String]) -> Result<Action, String> {
+    let mut inv = Invocation {
+        source: Source::Default,
+        arg0: None,
+        positional: Vec::new(),
+        interactive: false,
+        login: false,
+        options: Vec::new(),
+        lint: None,
+    };
+    let (mut command, mut stdin) = (false, false);
+    let mut i = 0;
+
+    while i < args.len() {
+        let a = args[i].as_str();
+        match a {
+            "--" => { i += 1; break; }
+            "--version" => return Ok(Action::Version),
+            "--help" => return Ok(Action::Help),
+            "--login" => inv.login = true,
+            "--lint" | "--lint=human" => inv.lint = Some(LintFormat::Human),
+            "--lint=json" => inv.lint = Some(LintFormat::Json),
+            "-o" | "+o" => {
+                let name = args.get(i + 1).ok_or_else(|| format!("{a}: option requires an argument"))?;
+                ShellOptions::default().set(name, true)?;
+                inv.options.push((name.clone(), a == "-o"));
+                i += 1;
+            }
+            _ if a.starts_with("--") => return Err(format!("{a}: invalid option")),
+            _ if (a.starts_with('-') || a.starts_with('+')) && a.len() > 1 => {
+                let on = a.starts_with('-');
+                for c in a[1..].chars() {
+                    match c {
+                        'c' if on => command = true,
+                        's' if on => stdin = true,
+                        'i' if on => inv.interactive = true,
+                        'l' if on => inv.login = true,
+                        _ => {
+                            let name = ShellOptions::name_for_letter(c)
+                                .ok_or_else(|| format!("{}{c}: invalid option", &a[..1]))?;
+                            inv.options.push((name.to_string(), on));
+                        }
+                    }
+                }
+            }
+            _ => break,
+        }
+        i += 1;
+    }
+    let mut operands = args[i..].iter().cloned();
+
+    inv.source = if command {
+        let cmd = operands.next().ok_or("-c: option requires an argument")?;
+        inv.arg0 = operands.next();
+        Source::Command(cmd)
+    } else if stdin {
+        Source::Stdin
+    } else {
+        match operands.next() {
+            Some(script) => Source::Script(script),
+            None => Source::Default,
+        }
+    };
+    inv.positional = operands.collect();
+    Ok(Action::Run(inv))
+}
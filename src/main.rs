@@ -1,18 +1,155 @@
+mod cli;
 mod prelude;
 mod shell;
 
 use crate::prelude::*;
+use shell::exec::status::{exit_status, Flow};
 use std::env;
+use std::io::IsTerminal;
 use std::path::PathBuf;
 
-fn main() -> Result<()> {
+fn main() {
     let args: Vec<String> = env::args().collect();
-    let mut sh = shell::Shell::new()?;
+    let inv = match cli::parse(&args[1..]) {
+        Ok(cli::Action::Run(inv)) => inv,
+        Ok(cli::Action::Version) => {
+            println!("0-shell, version {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Ok(cli::Action::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("0-shell: {e}");
+            eprintln!("{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
 
-    if args.len() > 1 {
-        let script_path = PathBuf::from(&args[1]);
-        shell::scripting::run_script_file(&mut sh, &script_path).map(|_| ())
-    } else {
-        sh.repl()
+    if let Some(format) = inv.lint {
+        std::process::exit(lint(inv.source, format));
     }
+
+    let mut sh = match shell::Shell::new() {
+        Ok(sh) => sh,
+        Err(e) => {
+            eprintln!("0-shell: {e}");
+            std::process::exit(2);
+        }
+    };
+    if inv.interactive || (inv.source == cli::Source::Default && io::stdin().is_terminal()) {
+        sh.enable_interactive();
+    }
+    for (name, on) in &inv.options {
+        if let Err(e) = sh.options.set(name, *on) {
+            eprintln!("0-shell: {e}");
+            std::process::exit(2);
+        }
+    }
+    sh.positional = inv.positional;
+    if let Some(arg0) = inv.arg0 {
+        sh.arg0 = arg0;
+    }
+
+    if inv.login {
+        if let Some(home) = sh.vars.get_str("HOME") {
+            let profile = PathBuf::from(home).join(".profile");
+            if profile.is_file() {
+                let last = sh.last_status;
+                let result = shell::scripting::run_script_file(&mut sh, &profile);
+                if let Err(e) = &result {
+                    if let Some(Flow::Exit(n)) = Flow::of(e) {
+                        std::process::exit(finish(&mut sh, n));
+                    }
+                }
+                report(result, last);
+            }
+        }
+    }
+
+    let result = match inv.source {
+        cli::Source::Command(cmd) => shell::scripting::run_script_string(&mut sh, "-c", &cmd),
+        cli::Source::Script(path) => {
+            sh.arg0 = path.clone();
+            match std::fs::read_to_string(&path) {
+                Ok(src) => shell::scripting::run_script_string(&mut sh, &path, &src),
+                Err(e) => {
+                    eprintln!("0-shell: {path}: {}", io_message(&e));
+                    // As in other shells: 127 for a missing script, 126 otherwise.
+                    let status = if e.kind() == io::ErrorKind::NotFound { 127 } else { 126 };
+                    std::process::exit(status);
+                }
+            }
+        }
+        cli::Source::Stdin => shell::scripting::runner::run_script_stdin(&mut sh),
+        cli::Source::Default => {
+            if sh.interactive {
+                sh.repl().map(|_| sh.last_status)
+            } else {
+                shell::scripting::runner::run_script_stdin(&mut sh)
+            }
+        }
+    };
+    // Scripts exit with their last status (2 after a syntax error), or
+    // the status given to `exit`.
+    let status = report(result, sh.last_status);
+    std::process::exit(finish(&mut sh, status));
+}
+
+/// The status `result` ends with; an error that got this far is reported
+/// and gives 1, it never leaves `main` as a Debug dump.
+fn report(result: Result<i32>, last: i32) -> i32 {
+    exit_status(result, last).unwrap_or_else(|e| {
+        eprintln!("0-shell: {e}");
+        1
+    })
+}
+
+/// Run the EXIT trap (which may `exit` with another status) and flush
+/// output. Returns the status the process exits with.
+fn finish(sh: &mut shell::Shell, status: i32) -> i32 {
+    sh.last_status = status;
+    let status = match exit_status(shell::signals::trap::run_exit(sh).map(|_| status), status) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("0-shell: {e}");
+            status
+        }
+    };
+    let _ = io::stdout().flush();
+    status
+}
+
+/// `No such file or directory` rather than io::Error's `... (os error 2)`.
+fn io_message(e: &io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::NotFound => "No such file or directory".into(),
+        io::ErrorKind::PermissionDenied => "Permission denied".into(),
+        io::ErrorKind::IsADirectory => "Is a directory".into(),
+        _ => e.to_string(),
+    }
+}
+
+/// `--lint`: check the script named by `source` without running it.
+fn lint(source: cli::Source, format: shell::scripting::lint::LintFormat) -> i32 {
+    let (origin, src) = match source {
+        cli::Source::Command(cmd) => ("-c".to_string(), cmd),
+        cli::Source::Script(path) => match std::fs::read_to_string(&path) {
+            Ok(src) => (path, src),
+            Err(e) => {
+                eprintln!("0-shell: {path}: {}", io_message(&e));
+                return 2;
+            }
+        },
+        cli::Source::Stdin | cli::Source::Default => {
+            let mut src = String::new();
+            if let Err(e) = io::Read::read_to_string(&mut io::stdin(), &mut src) {
+                eprintln!("0-shell: stdin: {}", io_message(&e));
+                return 2;
+            }
+            ("stdin".to_string(), src)
+        }
+    };
+    shell::scripting::lint::run_lint(&origin, &src, format)
 }
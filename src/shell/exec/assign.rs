@@ -0,0 +1,119 @@
+use super::expand::{eval_subscript, expand_word, expand_word_single};
+use crate::shell::parser::lexer::{tokenize, Token};
+use crate::shell::vars::{is_valid_name, Value};
+use crate::shell::Shell;
+use std::collections::BTreeMap;
+
+/// Left-hand side of an assignment word.
+pub struct Lhs<'a> {
+    pub name: &'a str,
+    pub sub: Option<&'a str>,
+    pub append: bool,
+}
+
+/// Split `name[sub]+=rhs` into its parts.
+pub fn parse_lhs(word: &str) -> Option<(Lhs<'_>, &str)> {
+    let eq = word.find('=')?;
+    let (lhs, rhs) = (&word[..eq], &word[eq + 1..]);
+    let (lhs, append) = match lhs.strip_suffix('+') {
+        Some(l) => (l, true),
+        None => (lhs, false),
+    };
+    let (name, sub) = match lhs.find('[') {
+        Some(b) if lhs.ends_with(']') => (&lhs[..b], Some(&lhs[b + 1..lhs.len() - 1])),
+        Some(_) => return None,
+        None => (lhs, None),
+    };
+    if !is_valid_name(name) { return None; }
+    Some((Lhs { name, sub, append }, rhs))
+}
+
+/// Perform an assignment word such as `x=1`, `a[3]=w`, `a+=(v)` or `m=([k]=v)`.
+pub fn apply_assignment(shell: &mut Shell, word: &str) -> Result<(), String> {
+    let (lhs, rhs) = parse_lhs(word).ok_or_else(|| format!("{word}: not a valid identifier"))?;
+    if lhs.sub.is_none() {
+        if let Some(inner) = rhs.strip_prefix('(').and_then(|r| r.strip_suffix(')')) {
+            return assign_compound(shell, &lhs, inner);
+        }
+    }
+    let value = expand_word_single(shell, rhs)?;
+    assign_expanded(shell, &lhs, value)
+}
+
+/// Assign an already-expanded scalar value.
+pub fn assign_expanded(shell: &mut Shell, lhs: &Lhs<'_>, value: String) -> Result<(), String> {
+    match lhs.sub {
+        Some(sub) => {
+            let key = eval_subscript(shell, lhs.name, sub)?;
+            let value = if lhs.append {
+                shell.vars.get_element(lhs.name, &key).unwrap_or_default() + &value
+            } else {
+                value
+            };
+            shell.vars.set_element(lhs.name, &key, value)
+        }
+        None => {
+            let value = if lhs.append {
+                shell.vars.get_str(lhs.name).unwrap_or_default().to_string() + &value
+            } else {
+                value
+            };
+            if lhs.name == "OPTIND" {
+                shell.getopts_state = Default::default();
+            }
+            shell.vars.set_scalar(lhs.name, value);
+            Ok(())
+        }
+    }
+}
+
+/// `name=( ... )` / `name+=( ... )`; `inner` is the raw text between the parens.
+pub fn assign_compound(shell: &mut Shell, lhs: &Lhs<'_>, inner: &str) -> Result<(), String> {
+    let is_assoc = matches!(shell.vars.get(lhs.name).map(|v| &v.value), Some(Value::Assoc(_)));
+    let words: Vec<String> = tokenize(inner).unwrap_or_default().into_iter()
+        .filter_map(|t| match t { Token::Word(w) => Some(w), _ => None })
+        .collect();
+
+    if is_assoc {
+        let mut map = match (&shell.vars.get(lhs.name).map(|v| &v.value), lhs.append) {
+            (Some(Value::Assoc(m)), true) => m.clone(),
+            _ => BTreeMap::new(),
+        };
+        for w in &words {
+            let (key, val) = keyed_element(w)
+                .ok_or_else(|| format!("{}: {w}: must use subscript when assigning associative array", lhs.name))?;
+            map.insert(expand_word_single(shell, key)?, expand_word_single(shell, val)?);
+        }
+        shell.vars.set_value(lhs.name, Value::Assoc(map));
+        return Ok(());
+    }
+
+    let mut map = match (&shell.vars.get(lhs.name).map(|v| &v.value), lhs.append) {
+        (Some(Value::Indexed(m)), true) => m.clone(),
+        (Some(Value::Scalar(s)), true) => BTreeMap::from([(0, s.clone())]),
+        _ => BTreeMap::new(),
+    };
+    let mut next = map.keys().next_back().map(|k| k + 1).unwrap_or(0);
+    for w in &words {
+        if let Some((key, val)) = keyed_element(w) {
+            let idx: usize = expand_word_single(shell, key)?.trim().parse()
+                .map_err(|_| format!("{key}: bad array subscript"))?;
+            map.insert(idx, expand_word_single(shell, val)?);
+            next = idx + 1;
+        } else {
+            for v in expand_word(shell, w)? {
+                map.insert(next, v);
+                next += 1;
+            }
+        }
+    }
+    shell.vars.set_value(lhs.name, Value::Indexed(map));
+    Ok(())
+}
+
+/// `[key]=value` inside a compound assignment.
+fn keyed_element(word: &str) -> Option<(&str, &str)> {
+    let rest = word.strip_prefix('[')?;
+    let close = rest.find("]=")?;
+    Some((&rest[..close], &rest[close + 2..]))
+}
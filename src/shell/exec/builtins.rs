@@ -1,27 +1,79 @@
-use super::{env::expand_vars, fileops::* , status::*};
+use super::{fileops::* , status::*};
 use crate::prelude::*;
 use std::env;
 use std::fs;
-use std::io::{Read, Write};
+use std::io::Read;
 use std::path::Path;
 
 pub fn is_builtin(name: &str) -> bool {
     matches!(name,
-        "exit" | "echo" | "pwd" | "cd" | "mkdir" | "ls" | "cat" | "cp" | "rm" | "mv" |
-        "jobs" | "fg" | "bg" | "kill" | "sleep" | "read"
+        "exit" | "return" | "break" | "continue" | "echo" | "pwd" | "cd" | "mkdir" | "ls" | "cat" | "cp" | "rm" | "mv" |
+        "jobs" | "fg" | "bg" | "kill" | "sleep" | "read" | "mapfile" | "readarray" |
+        "declare" | "typeset" | "export" | "unset" | "alias" | "unalias" |
+        "source" | "." | "eval" | "shift" | "set" | "shopt" | "trap" | "getopts" | "printf" |
+        "type" | "hash" | "which" | "command" | "builtin" | "exec" | "umask" | "ulimit"
     )
 }
 
+/// Builtins whose `name=value` operands are expanded like assignments.
+pub fn is_declaration_builtin(name: &str) -> bool {
+    matches!(name, "declare" | "typeset" | "export")
+}
+
+/// Runs a builtin and flushes what it wrote. A failed write (a full disk,
+/// a closed pipe) is reported as `cmd: write error` with status 1 rather
+/// than ending the shell; only `Flow` errors are passed on.
+pub fn run_builtin(shell: &mut crate::shell::Shell, cmd: String, args: &[String]) -> Result<i32> {
+    let result = dispatch_builtin(shell, cmd.clone(), args).and_then(|status| {
+        io::stdout().flush()?;
+        Ok(status)
+    });
+    let e = match result {
+        Err(e) if Flow::of(&e).is_none() => e,
+        other => return other,
+    };
+    match e.downcast_ref::<io::Error>() {
+        Some(io_err) => {
+            discard_stdout_buffer();
+            let msg = match io_err.raw_os_error() {
+                #[cfg(unix)]
+                Some(n) => nix::errno::Errno::from_raw(n).desc().to_string(),
+                _ => io_err.to_string(),
+            };
+            eprintln!("0-shell: {cmd}: write error: {msg}");
+        }
+        None => eprintln!("0-shell: {e}"),
+    }
+    Ok(err())
+}
+
+/// Drop what a failed write left in stdout's buffer, so it doesn't come
+/// out later wherever stdout points then: flush it into /dev/null.
+#[cfg(unix)]
+fn discard_stdout_buffer() {
+    use nix::unistd::{close, dup, dup2};
+    use std::os::fd::AsRawFd;
+    let Ok(null) = fs::OpenOptions::new().write(true).open("/dev/null") else { return };
+    let Ok(saved) = dup(1) else { return };
+    let _ = dup2(null.as_raw_fd(), 1);
+    let _ = io::stdout().flush();
+    let _ = dup2(saved, 1);
+    let _ = close(saved);
+}
+
+#[cfg(not(unix))]
+fn discard_stdout_buffer() {}
+
 /// Dispatches builtins. Returns an exit status.
-pub fn dispatch_builtin(shell: &mut crate::shell::Shell, cmd: String, args: &[String]) -> Result<i32> {
+fn dispatch_builtin(shell: &mut crate::shell::Shell, cmd: String, args: &[String]) -> Result<i32> {
     match cmd.as_str() {
-        "exit" => {
-            // Return special signal so REPL exits immediately
-            Ok(EXIT_SIGNAL)
-        }
+        "exit"   => cmd_exit(shell, args),
+        "return" => cmd_return(shell, args),
+        "break" | "continue" => cmd_loop_control(shell, &cmd, args),
         "echo" => cmd_echo(shell, args),
+        "printf" => super::printf::builtin_printf(shell, args),
         "pwd"  => cmd_pwd(),
-        "cd"   => cmd_cd(args),
+        "cd"   => cmd_cd(shell, args),
         "mkdir"=> cmd_mkdir(args),
         "ls"   => cmd_ls(args),
         "cat"  => cmd_cat(args),
@@ -37,7 +89,35 @@ pub fn dispatch_builtin(shell: &mut crate::shell::Shell, cmd: String, args: &[St
 
         // utilities for scripting demos
         "sleep" => cmd_sleep(args),
-        "read"  => cmd_read(shell, args),
+        #[cfg(unix)]
+        "umask" => super::limits::builtin_umask(args),
+        #[cfg(unix)]
+        "ulimit" => super::limits::builtin_ulimit(args),
+        "read"  => super::read::builtin_read(shell, args),
+        "mapfile" | "readarray" => super::mapfile::builtin_mapfile(shell, &cmd, args),
+
+        // variables
+        "declare" | "typeset" => super::declare::builtin_declare(shell, args),
+        "export" => super::declare::builtin_export(shell, args),
+        "unset"  => super::declare::builtin_unset(shell, args),
+
+        // aliases
+        "alias"   => crate::shell::parser::alias::builtin_alias(&mut shell.aliases, args),
+        "unalias" => crate::shell::parser::alias::builtin_unalias(&mut shell.aliases, args),
+
+        // scripting
+        "source" | "." => crate::shell::scripting::source::builtin_source(shell, &cmd, args),
+        "eval"   => crate::shell::scripting::source::builtin_eval(shell, args),
+        "shift"  => crate::shell::scripting::params::builtin_shift(shell, args),
+        "set"    => crate::shell::scripting::params::builtin_set(shell, args),
+        "getopts" => crate::shell::scripting::params::builtin_getopts(shell, args),
+        "shopt"  => crate::shell::options::builtin_shopt(&mut shell.options, args),
+        "trap"   => crate::shell::signals::trap::builtin_trap(shell, args),
+
+        // introspection (`command`, `builtin` and `exec` are run by the executor)
+        "type"   => super::lookup::builtin_type(shell, args),
+        "hash"   => super::lookup::builtin_hash(shell, args),
+        "which"  => super::lookup::builtin_which(shell, args),
         _ => {
             eprintln!("Command '{cmd}' not found");
             Ok(127)
@@ -45,21 +125,123 @@ pub fn dispatch_builtin(shell: &mut crate::shell::Shell, cmd: String, args: &[St
     }
 }
 
+/// exit [N]: leave the shell with status N, or `$?` (inside a trap action,
+/// `$?` from before the trap).
+fn cmd_exit(shell: &crate::shell::Shell, args: &[String]) -> Result<i32> {
+    let status = match args {
+        [] => shell.traps.status_before().unwrap_or(shell.last_status),
+        [n] => parse_status(n).unwrap_or_else(|| {
+            eprintln!("exit: {n}: numeric argument required");
+            2
+        }),
+        _ => {
+            eprintln!("exit: too many arguments");
+            1
+        }
+    };
+    Err(Flow::Exit(status).into())
+}
+
+/// return [N]: leave the current function or sourced file.
+fn cmd_return(shell: &crate::shell::Shell, args: &[String]) -> Result<i32> {
+    if shell.return_depth == 0 {
+        eprintln!("return: can only `return' from a function or sourced script");
+        return Ok(2);
+    }
+    let status = match args {
+        [] => shell.traps.status_before().unwrap_or(shell.last_status),
+        [n] => parse_status(n).unwrap_or_else(|| {
+            eprintln!("return: {n}: numeric argument required");
+            2
+        }),
+        _ => {
+            eprintln!("return: too many arguments");
+            return Ok(1);
+        }
+    };
+    Err(Flow::Return(status).into())
+}
+
+/// break [N] / continue [N]: leave, or go to the next iteration of, the
+/// Nth enclosing loop.
+fn cmd_loop_control(shell: &crate::shell::Shell, name: &str, args: &[String]) -> Result<i32> {
+    if shell.loop_depth == 0 {
+        eprintln!("{name}: only meaningful in a `for', `while', or `until' loop");
+        return Ok(0);
+    }
+    let n = match args.first().map(|a| (a, a.parse::<i64>())) {
+        None => 1,
+        Some((_, Ok(n))) if n >= 1 => n as usize,
+        Some((a, Ok(_))) => {
+            eprintln!("{name}: {a}: loop count out of range");
+            return Ok(1);
+        }
+        Some((a, Err(_))) => {
+            eprintln!("{name}: {a}: numeric argument required");
+            return Ok(1);
+        }
+    };
+    let n = n.min(shell.loop_depth);
+    Err(if name == "break" { Flow::Break(n) } else { Flow::Continue(n) }.into())
+}
+
+/// An `exit`/`return` status: any integer, taken modulo 256.
+fn parse_status(s: &str) -> Option<i32> {
+    s.trim().parse::<i64>().ok().map(|n| (n & 0xff) as i32)
+}
+
+/// echo [-neE] [ARGS...]. Only a leading run of words made of valid flags
+/// counts as options (`echo -x` prints `-x`). `-e`, or `shopt -s xpg_echo`,
+/// turns on backslash escapes; `\c` ends the output.
 fn cmd_echo(shell: &crate::shell::Shell, args: &[String]) -> Result<i32> {
-    let expanded = expand_vars(args, &shell.vars);
-    println!("{}", expanded.join(" "));
+    use super::printf::{expand_escapes, EscapeMode};
+    let mut newline = true;
+    let mut escapes = shell.options.get("xpg_echo");
+    let mut i = 0;
+    while let Some(flags) = args.get(i).and_then(|a| a.strip_prefix('-')) {
+        if flags.is_empty() || !flags.chars().all(|c| matches!(c, 'n' | 'e' | 'E')) {
+            break;
+        }
+        for c in flags.chars() {
+            match c {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+        i += 1;
+    }
+
+    let text = args[i..].join(" ");
+    let mut out = if escapes {
+        let (bytes, stop) = expand_escapes(&text, EscapeMode::Echo);
+        newline &= !stop;
+        bytes
+    } else {
+        text.into_bytes()
+    };
+    if newline {
+        out.push(b'\n');
+    }
+    io::stdout().lock().write_all(&out)?;
     Ok(ok())
 }
 
 fn cmd_pwd() -> Result<i32> {
-    let cwd = env::current_dir()?;
-    println!("{}", cwd.display());
+    let cwd = match env::current_dir() {
+        Ok(cwd) => cwd,
+        Err(e) => {
+            eprintln!("pwd: {e}");
+            return Ok(err());
+        }
+    };
+    writeln!(io::stdout(), "{}", cwd.display())?;
     Ok(ok())
 }
 
-fn cmd_cd(args: &[String]) -> Result<i32> {
+fn cmd_cd(shell: &crate::shell::Shell, args: &[String]) -> Result<i32> {
     let target = if args.is_empty() {
-        env::var("HOME").unwrap_or_else(|_| "/".into())
+        shell.vars.get_str("HOME").unwrap_or("/").to_string()
     } else {
         args[0].clone()
     };
@@ -109,6 +291,7 @@ fn parse_ls_flags(args: &[String]) -> (bool, bool, bool, Vec<String>) {
 fn cmd_ls(args: &[String]) -> Result<i32> {
     let (long, all, classify, paths) = parse_ls_flags(args);
     let targets = if paths.is_empty() { vec![".".to_string()] } else { paths };
+    let mut out = io::stdout().lock();
 
     for (i, t) in targets.iter().enumerate() {
         let path = Path::new(t);
@@ -121,12 +304,19 @@ fn cmd_ls(args: &[String]) -> Result<i32> {
         };
 
         if targets.len() > 1 {
-            if i > 0 { println!(); }
-            println!("{}:", t);
+            if i > 0 { writeln!(out)?; }
+            writeln!(out, "{}:", t)?;
         }
 
         if meta.is_dir() {
-            let mut entries: Vec<_> = fs::read_dir(path)?
+            let dir = match fs::read_dir(path) {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("ls: {}: {}", t, e);
+                    continue;
+                }
+            };
+            let mut entries: Vec<_> = dir
                 .filter_map(|e| e.ok())
                 .collect();
             entries.sort_by_key(|e| e.file_name());
@@ -137,31 +327,31 @@ fn cmd_ls(args: &[String]) -> Result<i32> {
                 if !all && is_hidden(&name) { continue; }
 
                 let p = e.path();
-                let md = e.metadata()?;
+                let Ok(md) = e.metadata() else { continue };
                 if long {
                     let mode = super::fileops::mode_string(&md);
                     let size = md.len();
-                    print!("{mode} {:>8} ", size);
+                    write!(out, "{mode} {:>8} ", size)?;
                 }
                 if classify {
-                    print!("{}{}", name, super::fileops::classify_suffix(&p, md.is_dir()));
+                    write!(out, "{}{}", name, super::fileops::classify_suffix(&p, md.is_dir()))?;
                 } else {
-                    print!("{name}");
+                    write!(out, "{name}")?;
                 }
-                println!();
+                writeln!(out)?;
             }
         } else {
             if long {
                 let mode = super::fileops::mode_string(&meta);
                 let size = meta.len();
-                print!("{mode} {:>8} ", size);
+                write!(out, "{mode} {:>8} ", size)?;
             }
             if classify {
-                print!("{}{}", t, super::fileops::classify_suffix(path, meta.is_dir()));
+                write!(out, "{}{}", t, super::fileops::classify_suffix(path, meta.is_dir()))?;
             } else {
-                print!("{t}");
+                write!(out, "{t}")?;
             }
-            println!();
+            writeln!(out)?;
         }
     }
 
@@ -171,8 +361,11 @@ fn cmd_ls(args: &[String]) -> Result<i32> {
 fn cmd_cat(args: &[String]) -> Result<i32> {
     if args.is_empty() {
         let mut buf = String::new();
-        io::stdin().read_to_string(&mut buf)?;
-        print!("{buf}");
+        if let Err(e) = io::stdin().read_to_string(&mut buf) {
+            eprintln!("cat: -: {e}");
+            return Ok(err());
+        }
+        io::stdout().write_all(buf.as_bytes())?;
         return Ok(ok());
     }
     for a in args {
@@ -184,8 +377,11 @@ fn cmd_cat(args: &[String]) -> Result<i32> {
             }
         };
         let mut buf = String::new();
-        f.read_to_string(&mut buf)?;
-        print!("{buf}");
+        if let Err(e) = f.read_to_string(&mut buf) {
+            eprintln!("cat: {}: {}", a, e);
+            return Ok(err());
+        }
+        io::stdout().write_all(buf.as_bytes())?;
     }
     Ok(ok())
 }
@@ -231,14 +427,14 @@ fn cmd_rm(args: &[String]) -> Result<i32> {
             Ok(m) => m,
             Err(e) => { eprintln!("rm: {}: {}", t, e); return Ok(err()); }
         };
-        if meta.is_dir() {
-            if !recursive {
-                eprintln!("rm: {}: is a directory (use -r)", t);
-                return Ok(err());
-            }
-            fs::remove_dir_all(t)?;
-        } else {
-            fs::remove_file(t)?;
+        if meta.is_dir() && !recursive {
+            eprintln!("rm: {}: is a directory (use -r)", t);
+            return Ok(err());
+        }
+        let removed = if meta.is_dir() { fs::remove_dir_all(t) } else { fs::remove_file(t) };
+        if let Err(e) = removed {
+            eprintln!("rm: {}: {}", t, e);
+            return Ok(err());
         }
     }
     Ok(ok())
@@ -261,20 +457,23 @@ fn cmd_mv(args: &[String]) -> Result<i32> {
 }
 
 fn cmd_sleep(args: &[String]) -> Result<i32> {
-    let secs: u64 = args.get(0).and_then(|s| s.parse().ok()).unwrap_or(1);
+    let secs: u64 = args.first().and_then(|s| s.parse().ok()).unwrap_or(1);
     std::thread::sleep(std::time::Duration::from_secs(secs));
     Ok(ok())
 }
 
-fn cmd_read(shell: &mut crate::shell::Shell, args: &[String]) -> Result<i32> {
-    // usage: read NAME
-    let Some(var) = args.get(0) else {
-        eprintln!("read: missing variable name");
-        return Ok(err());
-    };
-    let mut line = String::new();
-    io::stdin().read_line(&mut line)?;
-    let line = line.strip_suffix('\n').unwrap_or(&line).to_string();
-    shell.vars.insert(var.clone(), line);
-    Ok(ok())
+#[cfg(test)]
+mod tests {
+    use crate::shell::scripting::run_script_string;
+    use crate::shell::vars::VarStore;
+    use crate::shell::Shell;
+
+    #[cfg(unix)]
+    #[test]
+    fn a_failed_write_is_status_one_and_the_script_goes_on() {
+        let mut shell = Shell::with_vars(VarStore::default()).unwrap();
+        run_script_string(&mut shell, "test", "echo hi > /dev/full; s=$?; pwd > /dev/full; t=$?").unwrap();
+        assert_eq!(shell.vars.get_str("s"), Some("1"));
+        assert_eq!(shell.vars.get_str("t"), Some("1"));
+    }
 }
@@ -0,0 +1,207 @@
+use super::expand::{expand_pattern, expand_word_single, expand_words};
+use super::status::Flow;
+use super::read::FdInput;
+use super::{expansion_failed, run_list, run_subshell, without_errexit};
+use crate::prelude::*;
+use crate::shell::parser::ast::{CaseArm, CompoundCommand, List};
+use crate::shell::util::glob;
+use crate::shell::Shell;
+
+#[cfg(unix)]
+use nix::libc;
+
+/// Run `if`, loops, `case`, `{ }` and `( )`. Redirections are handled by the caller.
+pub fn run_compound(shell: &mut Shell, cmd: &CompoundCommand) -> Result<i32> {
+    match cmd {
+        CompoundCommand::Brace(list) => run_list(shell, list),
+        CompoundCommand::Subshell(list) => run_subshell(shell, list),
+        CompoundCommand::If { branches, otherwise } => {
+            for (cond, body) in branches {
+                let status = without_errexit(shell, |sh| run_list(sh, cond))?;
+                if status == 0 {
+                    return run_list(shell, body);
+                }
+            }
+            match otherwise {
+                Some(body) => run_list(shell, body),
+                None => Ok(0),
+            }
+        }
+        CompoundCommand::While { cond, body, until } => {
+            in_loop(shell, |sh| {
+                let mut status = 0;
+                loop {
+                    let c = without_errexit(sh, |sh| run_list(sh, cond))?;
+                    if (c == 0) == *until {
+                        break;
+                    }
+                    match loop_body(sh, body)? {
+                        Step::Next(s) => status = s,
+                        Step::Stop(s) => return Ok(s),
+                    }
+                }
+                Ok(status)
+            })
+        }
+        CompoundCommand::For { var, words, body, select } => {
+            let items = match words {
+                Some(words) => match expand_words(shell, words) {
+                    Ok(items) => items,
+                    Err(e) => return expansion_failed(shell, &e),
+                },
+                None => shell.positional.clone(),
+            };
+            if *select {
+                return run_select(shell, var, &items, body);
+            }
+            in_loop(shell, |sh| {
+                let mut status = 0;
+                for item in items {
+                    sh.vars.set_scalar(var, item);
+                    match loop_body(sh, body)? {
+                        Step::Next(s) => status = s,
+                        Step::Stop(s) => return Ok(s),
+                    }
+                }
+                Ok(status)
+            })
+        }
+        CompoundCommand::Case { word, arms } => run_case(shell, word, arms),
+    }
+}
+
+/// `select`: show the numbered menu on stderr, then read a line after the
+/// `PS3` prompt into `REPLY` and set `var` to the chosen item (empty if the
+/// line isn't one of the numbers). An empty line shows the menu again. Runs
+/// until `break`, or ends with status 1 at end of input.
+fn run_select(shell: &mut Shell, var: &str, items: &[String], body: &List) -> Result<i32> {
+    if items.is_empty() {
+        return Ok(0);
+    }
+    let mut input = FdInput::new(0);
+    in_loop(shell, |sh| {
+        let mut show_menu = true;
+        loop {
+            if show_menu {
+                print_menu(sh, items);
+            }
+            let prompt = sh.vars.get_str("PS3").unwrap_or("#? ").to_string();
+            eprint!("{prompt}");
+            let Some((line, _)) = input.read_until(b'\n')? else {
+                eprintln!();
+                return Ok(1);
+            };
+            let reply = String::from_utf8_lossy(&line).into_owned();
+            show_menu = reply.is_empty();
+            if show_menu {
+                continue;
+            }
+            let chosen = match reply.trim().parse::<usize>() {
+                Ok(n) if (1..=items.len()).contains(&n) => items[n - 1].clone(),
+                _ => String::new(),
+            };
+            sh.vars.set_scalar("REPLY", reply);
+            sh.vars.set_scalar(var, chosen);
+            if let Step::Stop(s) = loop_body(sh, body)? {
+                return Ok(s);
+            }
+        }
+    })
+}
+
+/// The menu as `N) item` entries in columns, numbered down each column,
+/// as many columns as fit in `COLUMNS` (or the terminal's width).
+fn print_menu(shell: &Shell, items: &[String]) {
+    let digits = items.len().to_string().len();
+    let widest = items.iter().map(|i| i.chars().count()).max().unwrap_or(0);
+    let entry = digits + 2 + widest;
+    let width = shell.vars.get_str("COLUMNS").and_then(|c| c.parse().ok()).unwrap_or_else(terminal_width);
+    let cols = (width / (entry + 2)).max(1);
+    let mut rows = items.len().div_ceil(cols);
+    let mut cols = items.len().div_ceil(rows);
+    if rows == 1 {
+        (rows, cols) = (cols, 1);
+    }
+    let mut out = String::new();
+    for row in 0..rows {
+        for col in 0..cols {
+            let i = col * rows + row;
+            let Some(item) = items.get(i) else { break };
+            let cell = format!("{:>w$}) {item}", i + 1, w = if col == 0 { rows.to_string().len() } else { digits });
+            if col + 1 < cols && items.get(i + rows).is_some() {
+                let pad = (entry + 2).saturating_sub(cell.chars().count());
+                out.push_str(&format!("{cell}{:pad$}", ""));
+            } else {
+                out.push_str(&cell);
+            }
+        }
+        out.push('\n');
+    }
+    eprint!("{out}");
+}
+
+/// Width of the terminal on stderr, 80 if it isn't one.
+#[cfg(unix)]
+fn terminal_width() -> usize {
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    match unsafe { libc::ioctl(2, libc::TIOCGWINSZ, &mut ws) } {
+        0 if ws.ws_col > 0 => ws.ws_col as usize,
+        _ => 80,
+    }
+}
+
+#[cfg(not(unix))]
+fn terminal_width() -> usize {
+    80
+}
+
+fn run_case(shell: &mut Shell, word: &str, arms: &[CaseArm]) -> Result<i32> {
+    let subject = match expand_word_single(shell, word) {
+        Ok(s) => s,
+        Err(e) => return expansion_failed(shell, &e),
+    };
+    for arm in arms {
+        for pat in &arm.patterns {
+            let pat = match expand_pattern(shell, pat) {
+                Ok(p) => p,
+                Err(e) => return expansion_failed(shell, &e),
+            };
+            if glob::matches(&pat, &subject) {
+                return run_list(shell, &arm.body);
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// What a loop does after its body ran.
+enum Step {
+    /// Go on (also after `continue`), the body's status so far.
+    Next(i32),
+    /// Leave the loop (`break`) with this status.
+    Stop(i32),
+}
+
+/// Run `f` as a loop that `break` and `continue` can reach.
+fn in_loop(shell: &mut Shell, f: impl FnOnce(&mut Shell) -> Result<i32>) -> Result<i32> {
+    shell.loop_depth += 1;
+    let r = f(shell);
+    shell.loop_depth -= 1;
+    r
+}
+
+/// Run one iteration's body. `break N`/`continue N` with N > 1 are passed
+/// on to the enclosing loop as N - 1.
+fn loop_body(shell: &mut Shell, body: &List) -> Result<Step> {
+    let err = match run_list(shell, body) {
+        Ok(status) => return Ok(Step::Next(status)),
+        Err(e) => e,
+    };
+    match Flow::of(&err) {
+        Some(Flow::Break(1)) => Ok(Step::Stop(0)),
+        Some(Flow::Continue(1)) => Ok(Step::Next(0)),
+        Some(Flow::Break(n)) => Err(Flow::Break(n - 1).into()),
+        Some(Flow::Continue(n)) => Err(Flow::Continue(n - 1).into()),
+        _ => Err(err),
+    }
+}
@@ -0,0 +1,192 @@
+use super::assign::{assign_compound, assign_expanded, parse_lhs};
+use super::status::*;
+use crate::prelude::*;
+use crate::shell::util::quote::double_quote;
+use crate::shell::vars::{is_valid_name, Value, Var};
+use crate::shell::Shell;
+
+/// declare/typeset [-aAx] [+x] [-p] [name[=value] ...]
+pub fn builtin_declare(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let (mut indexed, mut assoc, mut export, mut unexport, mut print) = (false, false, false, false, false);
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--" { i += 1; break; }
+        let (on, flags) = match (a.strip_prefix('-'), a.strip_prefix('+')) {
+            (Some(f), _) if !f.is_empty() => (true, f),
+            (_, Some(f)) if !f.is_empty() => (false, f),
+            _ => break,
+        };
+        for c in flags.chars() {
+            match (c, on) {
+                ('a', true) => indexed = true,
+                ('A', true) => assoc = true,
+                ('x', true) => export = true,
+                ('x', false) => unexport = true,
+                ('p', _) => print = true,
+                _ => {
+                    eprintln!("declare: -{c}: invalid option");
+                    eprintln!("declare: usage: declare [-aAxp] [name[=value] ...]");
+                    return Ok(2);
+                }
+            }
+        }
+        i += 1;
+    }
+    let names = &args[i..];
+
+    if print || names.is_empty() {
+        return print_declarations(shell, names, |v| {
+            (!indexed || matches!(v.value, Value::Indexed(_)))
+                && (!assoc || matches!(v.value, Value::Assoc(_)))
+                && (!export || v.exported)
+        });
+    }
+
+    let mut status = ok();
+    for operand in names {
+        if let Err(e) = declare_one(shell, operand, indexed, assoc) {
+            eprintln!("declare: {e}");
+            status = err();
+            continue;
+        }
+        let name = operand.split(['=', '[', '+']).next().unwrap_or_default();
+        if export { shell.vars.set_exported(name, true); }
+        if unexport { shell.vars.set_exported(name, false); }
+    }
+    Ok(status)
+}
+
+fn declare_one(shell: &mut Shell, operand: &str, indexed: bool, assoc: bool) -> std::result::Result<(), String> {
+    let Some((lhs, rhs)) = parse_lhs(operand) else {
+        if !is_valid_name(operand) {
+            return Err(format!("`{operand}': not a valid identifier"));
+        }
+        if indexed || assoc {
+            shell.vars.declare_array(operand, assoc)?;
+        } else if !shell.vars.contains(operand) {
+            shell.vars.set_scalar(operand, "");
+        }
+        return Ok(());
+    };
+    if indexed || assoc {
+        shell.vars.declare_array(lhs.name, assoc)?;
+    }
+    match rhs.strip_prefix('(').and_then(|r| r.strip_suffix(')')) {
+        Some(inner) if lhs.sub.is_none() => assign_compound(shell, &lhs, inner),
+        _ => assign_expanded(shell, &lhs, rhs.to_string()),
+    }
+}
+
+/// export [-n] [-p] [name[=value] ...]
+pub fn builtin_export(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut unexport = false;
+    let mut rest = args;
+    while let Some(a) = rest.first() {
+        match a.as_str() {
+            "-n" => unexport = true,
+            "-p" => {}
+            "--" => { rest = &rest[1..]; break; }
+            _ => break,
+        }
+        rest = &rest[1..];
+    }
+    if rest.is_empty() {
+        return print_declarations(shell, &[], |v| v.exported);
+    }
+
+    let mut status = ok();
+    for operand in rest {
+        let name = match parse_lhs(operand) {
+            Some((lhs, rhs)) => {
+                let name = lhs.name.to_string();
+                let res = match rhs.strip_prefix('(').and_then(|r| r.strip_suffix(')')) {
+                    Some(inner) if lhs.sub.is_none() => assign_compound(shell, &lhs, inner),
+                    _ => assign_expanded(shell, &lhs, rhs.to_string()),
+                };
+                if let Err(e) = res {
+                    eprintln!("export: {e}");
+                    status = err();
+                    continue;
+                }
+                name
+            }
+            None if is_valid_name(operand) => operand.clone(),
+            None => {
+                eprintln!("export: `{operand}': not a valid identifier");
+                status = err();
+                continue;
+            }
+        };
+        shell.vars.set_exported(&name, !unexport);
+    }
+    Ok(status)
+}
+
+/// unset [-v] name[sub] ...
+pub fn builtin_unset(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut status = ok();
+    for a in args.iter().filter(|a| a.as_str() != "-v") {
+        let (name, sub) = match a.find('[') {
+            Some(b) if a.ends_with(']') => (&a[..b], Some(&a[b + 1..a.len() - 1])),
+            _ => (a.as_str(), None),
+        };
+        if !is_valid_name(name) {
+            eprintln!("unset: `{a}': not a valid identifier");
+            status = err();
+            continue;
+        }
+        match sub {
+            Some("@") | Some("*") | None => shell.vars.unset(name),
+            Some(sub) => match super::expand::eval_subscript(shell, name, sub) {
+                Ok(key) => shell.vars.unset_element(name, &key),
+                Err(e) => { eprintln!("unset: {e}"); status = err(); }
+            },
+        }
+    }
+    Ok(status)
+}
+
+/// Print `declare -<flags> name=value` lines that can be read back in.
+fn print_declarations(shell: &Shell, names: &[String], filter: impl Fn(&Var) -> bool) -> Result<i32> {
+    let mut status = ok();
+    let names: Vec<String> = if names.is_empty() { shell.vars.names() } else { names.to_vec() };
+    let mut out = io::stdout().lock();
+    for name in &names {
+        let Some(var) = shell.vars.get(name) else {
+            eprintln!("declare: {name}: not found");
+            status = err();
+            continue;
+        };
+        if !filter(var) { continue; }
+        writeln!(out, "declare {} {}", flags_of(var), format_assignment(name, &var.value))?;
+    }
+    Ok(status)
+}
+
+fn flags_of(var: &Var) -> String {
+    let mut f = String::from("-");
+    match var.value {
+        Value::Indexed(_) => f.push('a'),
+        Value::Assoc(_) => f.push('A'),
+        Value::Scalar(_) => {}
+    }
+    if var.exported { f.push('x'); }
+    if f == "-" { f.push('-'); }
+    f
+}
+
+/// `name="v"`, `name=([0]="x" [1]="y")` or `name=([k]="v" )`.
+pub fn format_assignment(name: &str, value: &Value) -> String {
+    match value {
+        Value::Scalar(s) => format!("{name}={}", double_quote(s)),
+        Value::Indexed(m) => {
+            let items: Vec<String> = m.iter().map(|(k, v)| format!("[{k}]={}", double_quote(v))).collect();
+            format!("{name}=({})", items.join(" "))
+        }
+        Value::Assoc(m) => {
+            let items: String = m.iter().map(|(k, v)| format!("[{k}]={} ", double_quote(v))).collect();
+            format!("{name}=({items})")
+        }
+    }
+}
@@ -0,0 +1,582 @@
+use crate::shell::util::glob;
+use crate::shell::vars::{is_valid_name, Value};
+use crate::shell::Shell;
+use std::fmt;
+
+/// Why an expansion failed.
+#[derive(Debug)]
+pub enum ExpandError {
+    /// An unset parameter under `set -u`; aborts a non-interactive shell.
+    Unbound(String),
+    /// Bad substitutions, subscripts and slices.
+    Other(String),
+}
+
+impl fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpandError::Unbound(name) => write!(f, "{name}: unbound variable"),
+            ExpandError::Other(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for ExpandError {}
+
+impl From<String> for ExpandError {
+    fn from(msg: String) -> Self {
+        ExpandError::Other(msg)
+    }
+}
+
+impl From<ExpandError> for String {
+    fn from(e: ExpandError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Result of a parameter expansion: one string, or a list from `@`/`*`.
+enum Param {
+    Str(String),
+    List { items: Vec<String>, star: bool },
+    /// The word of `${name:-word}`, expanded where the parameter appears.
+    Word(String),
+}
+
+/// Accumulates fields while walking a word.
+struct Fields {
+    out: Vec<String>,
+    cur: String,
+    /// `cur` is a real field even when empty (e.g. it came from `""`).
+    has: bool,
+    /// Backslash-escape glob characters in quoted text (pattern contexts).
+    escape: bool,
+}
+
+impl Fields {
+    fn new(escape: bool) -> Self {
+        Self { out: Vec::new(), cur: String::new(), has: false, escape }
+    }
+
+    fn push_quoted(&mut self, s: &str) {
+        if self.escape {
+            for c in s.chars() {
+                if matches!(c, '*' | '?' | '[' | ']' | '\\') { self.cur.push('\\'); }
+                self.cur.push(c);
+            }
+        } else {
+            self.cur.push_str(s);
+        }
+        self.has = true;
+    }
+
+    fn finish(&mut self) {
+        if self.has || !self.cur.is_empty() {
+            self.out.push(std::mem::take(&mut self.cur));
+        }
+        self.has = false;
+    }
+
+    /// Unquoted expansion text is split on IFS. Its glob characters stay
+    /// active, but a backslash in it is just a character.
+    fn push_split(&mut self, s: &str, ifs: &str) {
+        for c in s.chars() {
+            if self.escape && c == '\\' {
+                self.cur.push_str("\\\\");
+                self.has = true;
+            } else if ifs.contains(c) {
+                if c.is_whitespace() {
+                    self.finish();
+                } else {
+                    self.has = true;
+                    self.finish();
+                }
+            } else {
+                self.cur.push(c);
+                self.has = true;
+            }
+        }
+    }
+
+    fn into_fields(mut self) -> Vec<String> {
+        self.finish();
+        self.out
+    }
+}
+
+/// Expand every word of a command line (parameters, field splitting, quote removal).
+pub fn expand_words(shell: &Shell, words: &[String]) -> Result<Vec<String>, ExpandError> {
+    let mut out = Vec::new();
+    for w in words {
+        out.extend(expand_word(shell, w)?);
+    }
+    Ok(out)
+}
+
+/// Expand one word into zero or more fields, including pathname expansion.
+pub fn expand_word(shell: &Shell, word: &str) -> Result<Vec<String>, ExpandError> {
+    let mut out = Vec::new();
+    for field in expand(shell, word, true, true, false)? {
+        if !shell.options.get("noglob") && glob::has_magic(&field) {
+            let paths = glob::expand_path(&field, shell.options.get("dotglob"));
+            if !paths.is_empty() {
+                out.extend(paths);
+                continue;
+            }
+            if shell.options.get("nullglob") {
+                continue;
+            }
+        }
+        out.push(glob::unescape(&field));
+    }
+    Ok(out)
+}
+
+/// Expand one word without field splitting (assignment values, subscripts).
+pub fn expand_word_single(shell: &Shell, word: &str) -> Result<String, ExpandError> {
+    Ok(expand(shell, word, false, false, false)?.join(" "))
+}
+
+/// Expand a `case` pattern: no field splitting, and quoted glob characters
+/// are escaped so they match literally.
+pub fn expand_pattern(shell: &Shell, word: &str) -> Result<String, ExpandError> {
+    Ok(expand(shell, word, false, true, false)?.join(" "))
+}
+
+/// IFS, or space-tab-newline when unset.
+pub fn ifs(shell: &Shell) -> String {
+    shell.vars.get_str("IFS").map(str::to_string).unwrap_or_else(|| " \t\n".into())
+}
+
+/// `split_text` also splits the word's own unquoted text, as for the word
+/// of an unquoted `${name:-word}`.
+fn expand(shell: &Shell, word: &str, split: bool, pattern: bool, split_text: bool) -> Result<Vec<String>, ExpandError> {
+    let chars: Vec<char> = word.chars().collect();
+    let ifs = if split { ifs(shell) } else { String::new() };
+    let mut f = Fields::new(pattern);
+    let mut i = 0;
+
+    if chars.first() == Some(&'~') {
+        let end = chars.iter().position(|&c| c == '/').unwrap_or(chars.len());
+        if end == 1 {
+            if let Some(home) = shell.vars.get_str("HOME") {
+                f.push_quoted(home);
+                i = 1;
+            }
+        }
+    }
+
+    while i < chars.len() {
+        match chars[i] {
+            '\'' => {
+                let end = chars[i + 1..].iter().position(|&c| c == '\'').map(|p| i + 1 + p).unwrap_or(chars.len());
+                let s: String = chars[i + 1..end].iter().collect();
+                f.push_quoted(&s);
+                i = end + 1;
+            }
+            '"' => {
+                i += 1;
+                let mut saw_empty_list = false;
+                let mut any_text = false;
+                while i < chars.len() && chars[i] != '"' {
+                    match chars[i] {
+                        '\\' if matches!(chars.get(i + 1), Some('$' | '"' | '\\' | '`' | '\n')) => {
+                            f.push_quoted(&chars[i + 1].to_string());
+                            any_text = true;
+                            i += 2;
+                        }
+                        '$' => {
+                            let (param, next) = parse_dollar(shell, &chars, i)?;
+                            i = next;
+                            match param {
+                                Param::Str(s) => { f.push_quoted(&s); any_text = true; }
+                                // Inside `"..."` the word is double-quoted too: its
+                                // single quotes are plain characters.
+                                Param::Word(w) => {
+                                    f.push_quoted(&expand_word_single(shell, &format!("\"{w}\""))?);
+                                    any_text = true;
+                                }
+                                Param::List { items, star: true } => {
+                                    f.push_quoted(&items.join(&join_sep(shell)));
+                                    any_text = true;
+                                }
+                                Param::List { items, star: false } => {
+                                    if items.is_empty() { saw_empty_list = true; }
+                                    for (n, item) in items.iter().enumerate() {
+                                        if n > 0 { f.has = true; f.finish(); }
+                                        f.push_quoted(item);
+                                    }
+                                    any_text |= !items.is_empty();
+                                }
+                            }
+                        }
+                        c => {
+                            f.push_quoted(&c.to_string());
+                            any_text = true;
+                            i += 1;
+                        }
+                    }
+                }
+                // `""` is an empty field, but `"$@"` with nothing in it is no field at all.
+                if !saw_empty_list || any_text {
+                    f.has = true;
+                }
+                i += 1;
+            }
+            '\\' => {
+                if let Some(&c) = chars.get(i + 1) {
+                    f.push_quoted(&c.to_string());
+                }
+                i += 2;
+            }
+            '$' => {
+                let (param, next) = parse_dollar(shell, &chars, i)?;
+                i = next;
+                match param {
+                    Param::Str(s) => {
+                        if split { f.push_split(&s, &ifs) } else { f.cur.push_str(&s) }
+                    }
+                    // Already split and quote-removed by its own expansion.
+                    Param::Word(w) => {
+                        for (n, field) in expand(shell, &w, split, pattern, true)?.into_iter().enumerate() {
+                            if n > 0 { f.finish(); }
+                            f.cur.push_str(&field);
+                            f.has = true;
+                        }
+                    }
+                    Param::List { items, star } => {
+                        if !split {
+                            let sep = if star { join_sep(shell) } else { " ".into() };
+                            f.cur.push_str(&items.join(&sep));
+                            continue;
+                        }
+                        for (n, item) in items.iter().enumerate() {
+                            if n > 0 { f.finish(); }
+                            f.push_split(item, &ifs);
+                        }
+                    }
+                }
+            }
+            c => {
+                if split_text {
+                    f.push_split(&c.to_string(), &ifs);
+                } else {
+                    f.cur.push(c);
+                    f.has = true;
+                }
+                i += 1;
+            }
+        }
+    }
+
+    let mut fields = f.into_fields();
+    if !split && fields.is_empty() {
+        fields.push(String::new());
+    }
+    Ok(fields)
+}
+
+/// Separator for `$*` / `${a[*]}`: the first character of IFS.
+fn join_sep(shell: &Shell) -> String {
+    match shell.vars.get_str("IFS") {
+        Some(s) => s.chars().next().map(String::from).unwrap_or_default(),
+        None => " ".into(),
+    }
+}
+
+/// Parse a `$...` at `chars[i]`; returns the expansion and the index after it.
+fn parse_dollar(shell: &Shell, chars: &[char], i: usize) -> Result<(Param, usize), ExpandError> {
+    match chars.get(i + 1) {
+        Some('{') => {
+            let mut depth = 1;
+            let mut j = i + 2;
+            while j < chars.len() {
+                match chars[j] {
+                    '{' => depth += 1,
+                    '}' => {
+                        depth -= 1;
+                        if depth == 0 { break; }
+                    }
+                    _ => {}
+                }
+                j += 1;
+            }
+            let inner: String = chars[i + 2..j.min(chars.len())].iter().collect();
+            if j >= chars.len() {
+                return Err(format!("${{{inner}: bad substitution").into());
+            }
+            Ok((expand_braced(shell, &inner)?, j + 1))
+        }
+        Some(&c) if matches!(c, '?' | '$' | '#' | '@' | '*' | '-') || c.is_ascii_digit() => {
+            let param = special(shell, &c.to_string())?.unwrap_or(Param::Str(String::new()));
+            Ok((param, i + 2))
+        }
+        Some(&c) if c == '_' || c.is_ascii_alphabetic() => {
+            let mut j = i + 1;
+            while j < chars.len() && (chars[j] == '_' || chars[j].is_ascii_alphanumeric()) {
+                j += 1;
+            }
+            let name: String = chars[i + 1..j].iter().collect();
+            Ok((Param::Str(scalar(shell, &name)?), j))
+        }
+        _ => Ok((Param::Str("$".into()), i + 1)),
+    }
+}
+
+/// Special and positional parameters: `$?`, `$$`, `$#`, `$@`, `$*`, `$-`, `$0`, `$1`...
+fn special(shell: &Shell, name: &str) -> Result<Option<Param>, ExpandError> {
+    Ok(Some(match name {
+        "?" => Param::Str(shell.last_status.to_string()),
+        "$" => Param::Str(std::process::id().to_string()),
+        "#" => Param::Str(shell.positional.len().to_string()),
+        "@" | "*" => Param::List { items: shell.positional.clone(), star: name == "*" },
+        "-" => {
+            let mut flags = shell.options.flags();
+            if shell.interactive { flags.push('i'); }
+            Param::Str(flags)
+        }
+        "0" => Param::Str(shell.arg0.clone()),
+        n if n.chars().all(|c| c.is_ascii_digit()) && !n.is_empty() => {
+            let Ok(k) = n.parse::<usize>() else { return Ok(None) };
+            match shell.positional.get(k.wrapping_sub(1)) {
+                Some(v) => Param::Str(v.clone()),
+                None if shell.options.get("nounset") => return Err(ExpandError::Unbound(n.to_string())),
+                None => Param::Str(String::new()),
+            }
+        }
+        _ => return Ok(None),
+    }))
+}
+
+/// Value of `name`; an unset name is an error under `set -u`.
+fn scalar(shell: &Shell, name: &str) -> Result<String, ExpandError> {
+    match shell.vars.get_str(name) {
+        Some(v) => Ok(v.to_string()),
+        None if shell.options.get("nounset") && !shell.vars.contains(name) => Err(ExpandError::Unbound(name.to_string())),
+        None => Ok(String::new()),
+    }
+}
+
+
+/// Split `name[sub]rest` into (name, Some(sub), rest); `name rest` otherwise.
+fn split_name(s: &str) -> (&str, Option<&str>, &str) {
+    let end = s.find(|c: char| !(c == '_' || c.is_ascii_alphanumeric())).unwrap_or(s.len());
+    let (name, rest) = s.split_at(end);
+    if let Some(after) = rest.strip_prefix('[') {
+        if let Some(close) = after.find(']') {
+            return (name, Some(&after[..close]), &after[close + 1..]);
+        }
+    }
+    (name, None, rest)
+}
+
+/// Split `param:-word` (or `-`, `:+`, `+`) into the parameter, whether
+/// there was a colon, the operator and the word.
+fn split_operator(inner: &str) -> Option<(&str, bool, char, &str)> {
+    let end = match inner.chars().next()? {
+        c if c.is_ascii_digit() => inner.find(|c: char| !c.is_ascii_digit()).unwrap_or(inner.len()),
+        '@' | '*' | '?' | '$' => 1,
+        _ => {
+            let (name, _, tail) = split_name(inner);
+            if !is_valid_name(name) { return None; }
+            inner.len() - tail.len()
+        }
+    };
+    let (param, rest) = inner.split_at(end);
+    let (colon, rest) = match rest.strip_prefix(':') {
+        Some(r) => (true, r),
+        None => (false, rest),
+    };
+    let op = rest.chars().next().filter(|c| matches!(c, '-' | '+'))?;
+    Some((param, colon, op, &rest[1..]))
+}
+
+/// Value of `param` for the operators above, None when it is unset. Never
+/// an unbound error: testing whether something is set is allowed under `set -u`.
+fn param_value(shell: &Shell, param: &str) -> Result<Option<String>, ExpandError> {
+    if let Ok(k) = param.parse::<usize>() {
+        return Ok(if k == 0 { Some(shell.arg0.clone()) } else { shell.positional.get(k - 1).cloned() });
+    }
+    let (name, sub, _) = split_name(param);
+    Ok(match sub {
+        _ if matches!(param, "@" | "*") => (!shell.positional.is_empty()).then(|| shell.positional.join(" ")),
+        _ if matches!(param, "?" | "$") => match special(shell, param)? {
+            Some(Param::Str(s)) => Some(s),
+            _ => None,
+        },
+        Some("@" | "*") => shell.vars.get(name).map(|v| v.value.values()).filter(|v| !v.is_empty()).map(|v| v.join(" ")),
+        Some(sub) => element(shell, name, sub)?,
+        None => shell.vars.get_str(name).map(str::to_string),
+    })
+}
+
+/// Body of `${...}`.
+fn expand_braced(shell: &Shell, inner: &str) -> Result<Param, ExpandError> {
+    let bad = || format!("${{{inner}}}: bad substitution");
+
+    if let Some(p) = special(shell, inner)? {
+        return Ok(p);
+    }
+
+    // ${param-word}, ${param:-word}, ${param+word}, ${param:+word}
+    if let Some((param, colon, op, word)) = split_operator(inner) {
+        let set = match param_value(shell, param)? {
+            Some(v) => !colon || !v.is_empty(),
+            None => false,
+        };
+        return match (op, set) {
+            ('-', true) => expand_braced(shell, param),
+            ('-', false) | ('+', true) => Ok(Param::Word(word.to_string())),
+            _ => Ok(Param::Str(String::new())),
+        };
+    }
+
+    // ${@:offset:length} counts $0 as position 0.
+    if let Some(spec) = inner.strip_prefix("@:").or_else(|| inner.strip_prefix("*:")) {
+        let mut all = vec![shell.arg0.clone()];
+        all.extend(shell.positional.iter().cloned());
+        let (off, len) = match spec.split_once(':') {
+            Some((o, l)) => (o, Some(parse_int(shell, l).ok_or_else(bad)?)),
+            None => (spec, None),
+        };
+        let off = parse_int(shell, off).ok_or_else(bad)?;
+        let items = slice(&all, off, len)?.to_vec();
+        return Ok(Param::List { items, star: inner.starts_with('*') });
+    }
+
+    // ${#name}, ${#name[@]}, ${#name[i]}, ${#1}, ${#@}
+    if let Some(rest) = inner.strip_prefix('#') {
+        match special(shell, rest)? {
+            Some(Param::Str(s)) => return Ok(Param::Str(s.chars().count().to_string())),
+            Some(Param::List { items, .. }) => return Ok(Param::Str(items.len().to_string())),
+            _ => {}
+        }
+        if !rest.is_empty() {
+            let (name, sub, tail) = split_name(rest);
+            if !is_valid_name(name) || !tail.is_empty() { return Err(bad().into()); }
+            let n = match sub {
+                Some("@") | Some("*") => shell.vars.get(name).map(|v| v.value.len()).unwrap_or(0),
+                Some(sub) => element(shell, name, sub)?.unwrap_or_default().chars().count(),
+                None => scalar(shell, name)?.chars().count(),
+            };
+            return Ok(Param::Str(n.to_string()));
+        }
+    }
+
+    // ${!name[@]}
+    if let Some(rest) = inner.strip_prefix('!') {
+        let (name, sub, tail) = split_name(rest);
+        return match sub {
+            Some(s @ ("@" | "*")) if is_valid_name(name) && tail.is_empty() => Ok(Param::List {
+                items: shell.vars.get(name).map(|v| v.value.keys()).unwrap_or_default(),
+                star: s == "*",
+            }),
+            _ => Err(bad().into()),
+        };
+    }
+
+    let (name, sub, tail) = split_name(inner);
+    if !is_valid_name(name) { return Err(bad().into()); }
+
+    let base = match sub {
+        Some(s @ ("@" | "*")) => Param::List {
+            items: shell.vars.get(name).map(|v| v.value.values()).unwrap_or_default(),
+            star: s == "*",
+        },
+        Some(sub) => match element(shell, name, sub)? {
+            Some(v) => Param::Str(v),
+            None if shell.options.get("nounset") => return Err(ExpandError::Unbound(format!("{name}[{sub}]"))),
+            None => Param::Str(String::new()),
+        },
+        None => Param::Str(scalar(shell, name)?),
+    };
+
+    if tail.is_empty() {
+        return Ok(base);
+    }
+    // ${name:offset} / ${name:offset:length}
+    let Some(spec) = tail.strip_prefix(':') else { return Err(bad().into()) };
+    let (off, len) = match spec.split_once(':') {
+        Some((o, l)) => (o, Some(l)),
+        None => (spec, None),
+    };
+    let off = parse_int(shell, off).ok_or_else(bad)?;
+    let len = match len {
+        Some(l) => Some(parse_int(shell, l).ok_or_else(bad)?),
+        None => None,
+    };
+
+    Ok(match base {
+        word @ Param::Word(_) => word,
+        Param::Str(s) => {
+            let chars: Vec<char> = s.chars().collect();
+            Param::Str(slice(&chars, off, len)?.iter().collect())
+        }
+        Param::List { star, .. } => {
+            let items = match shell.vars.get(name).map(|v| &v.value) {
+                // Indexed arrays slice by subscript, not by position.
+                Some(Value::Indexed(m)) => {
+                    let top = m.keys().next_back().map(|k| *k as i64 + 1).unwrap_or(0);
+                    let start = if off < 0 { top + off } else { off };
+                    if start < 0 {
+                        Vec::new()
+                    } else {
+                        let from: Vec<String> = m.range(start as usize..).map(|(_, v)| v.clone()).collect();
+                        slice(&from, 0, len)?.to_vec()
+                    }
+                }
+                Some(v) => slice(&v.values(), off, len)?.to_vec(),
+                None => Vec::new(),
+            };
+            Param::List { items, star }
+        }
+    })
+}
+
+/// Offset/length slicing with bash's negative-value rules.
+fn slice<T>(items: &[T], off: i64, len: Option<i64>) -> Result<&[T], ExpandError> {
+    let n = items.len() as i64;
+    let start = if off < 0 { (n + off).max(0) } else { off.min(n) };
+    let end = match len {
+        None => n,
+        Some(l) if l < 0 => {
+            let e = n + l;
+            if e < start { return Err(format!("{l}: substring expression < 0").into()); }
+            e
+        }
+        Some(l) => (start + l).min(n),
+    };
+    Ok(&items[start as usize..end as usize])
+}
+
+/// Integer operand of a slice or subscript: a literal or a variable name.
+fn parse_int(shell: &Shell, s: &str) -> Option<i64> {
+    let s = s.trim();
+    if let Ok(n) = s.parse() {
+        return Some(n);
+    }
+    let v = expand_word_single(shell, s).ok()?;
+    let v = v.trim();
+    if is_valid_name(v) {
+        return Some(shell.vars.get_str(v).and_then(|x| x.trim().parse().ok()).unwrap_or(0));
+    }
+    v.parse().ok()
+}
+
+/// `name[sub]` lookup: associative keys are expanded strings, indexed
+/// subscripts evaluate to integers.
+fn element(shell: &Shell, name: &str, sub: &str) -> Result<Option<String>, ExpandError> {
+    let key = eval_subscript(shell, name, sub)?;
+    Ok(shell.vars.get_element(name, &key))
+}
+
+/// Evaluate a subscript for `name`.
+pub fn eval_subscript(shell: &Shell, name: &str, sub: &str) -> Result<String, ExpandError> {
+    let is_assoc = matches!(shell.vars.get(name).map(|v| &v.value), Some(Value::Assoc(_)));
+    if is_assoc {
+        return expand_word_single(shell, sub);
+    }
+    parse_int(shell, sub)
+        .map(|n| n.to_string())
+        .ok_or_else(|| format!("{name}[{sub}]: bad array subscript").into())
+}
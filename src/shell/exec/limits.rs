@@ -0,0 +1,257 @@
+use crate::prelude::*;
+
+#[cfg(unix)]
+use nix::sys::resource::{getrlimit, setrlimit, Resource, RLIM_INFINITY};
+#[cfg(unix)]
+use nix::sys::stat::{umask, Mode};
+
+/// The file creation mask, without changing it.
+#[cfg(unix)]
+fn current_umask() -> u32 {
+    let old = umask(Mode::empty());
+    umask(old);
+    old.bits() as u32
+}
+
+/// `u=rwx,g=rx,o=rx` for the permissions a mask leaves.
+fn symbolic(mask: u32) -> String {
+    let perm = !mask & 0o777;
+    let part = |shift: u32| {
+        let bits = (perm >> shift) & 7;
+        [(4, 'r'), (2, 'w'), (1, 'x')].iter().filter(|(b, _)| bits & b != 0).map(|(_, c)| *c).collect::<String>()
+    };
+    format!("u={},g={},o={}", part(6), part(3), part(0))
+}
+
+/// Apply a symbolic mode like `u=rwx,g-w,o=` to `mask`.
+fn parse_symbolic(mode: &str, mask: u32) -> std::result::Result<u32, String> {
+    let mut perm = !mask & 0o777;
+    for clause in mode.split(',') {
+        let mut chars = clause.chars().peekable();
+        let mut who = 0;
+        while let Some(c) = chars.next_if(|c| "ugoa".contains(*c)) {
+            who |= match c {
+                'u' => 0o700,
+                'g' => 0o070,
+                'o' => 0o007,
+                _ => 0o777,
+            };
+        }
+        if who == 0 {
+            who = 0o777;
+        }
+        loop {
+            let op = match chars.next() {
+                Some(op @ ('+' | '-' | '=')) => op,
+                Some(c) => return Err(format!("`{c}': invalid symbolic mode operator")),
+                None => return Err(format!("`{clause}': invalid symbolic mode operator")),
+            };
+            let mut bits = 0;
+            while let Some(c) = chars.next_if(|c| !"+-=".contains(*c)) {
+                bits |= match c {
+                    'r' => 0o444,
+                    'w' => 0o222,
+                    'x' | 'X' => 0o111,
+                    's' | 't' => 0,
+                    _ => return Err(format!("`{c}': invalid symbolic mode character")),
+                };
+            }
+            bits &= who;
+            match op {
+                '+' => perm |= bits,
+                '-' => perm &= !bits,
+                _ => perm = (perm & !who) | bits,
+            }
+            if chars.peek().is_none() {
+                break;
+            }
+        }
+    }
+    Ok(!perm & 0o777)
+}
+
+/// umask [-pS] [mode]: show or set the file creation mask. `mode` is octal
+/// (`022`) or symbolic (`u=rwx,g=rx,o=`, `g-w`).
+#[cfg(unix)]
+pub fn builtin_umask(args: &[String]) -> Result<i32> {
+    let (mut sym, mut reusable) = (false, false);
+    let mut i = 0;
+    while let Some(a) = args.get(i).filter(|a| a.starts_with('-') && a.len() > 1) {
+        i += 1;
+        if a == "--" {
+            break;
+        }
+        for c in a.chars().skip(1) {
+            match c {
+                'S' => sym = true,
+                'p' => reusable = true,
+                _ => {
+                    eprintln!("umask: -{c}: invalid option");
+                    eprintln!("umask: usage: umask [-p] [-S] [mode]");
+                    return Ok(2);
+                }
+            }
+        }
+    }
+
+    if let Some(mode) = args.get(i) {
+        let mask = if mode.starts_with(|c: char| c.is_ascii_digit()) {
+            match u32::from_str_radix(mode, 8) {
+                Ok(m) if m <= 0o7777 => m & 0o777,
+                _ => {
+                    eprintln!("umask: {mode}: octal number out of range");
+                    return Ok(1);
+                }
+            }
+        } else {
+            match parse_symbolic(mode, current_umask()) {
+                Ok(m) => m,
+                Err(e) => {
+                    eprintln!("umask: {e}");
+                    return Ok(1);
+                }
+            }
+        };
+        umask(Mode::from_bits_truncate(mask as _));
+        if !sym {
+            return Ok(0);
+        }
+    }
+
+    let mask = current_umask();
+    let shown = if sym { symbolic(mask) } else { format!("{mask:04o}") };
+    let mut out = io::stdout().lock();
+    if reusable {
+        let flag = if sym { "-S " } else { "" };
+        writeln!(out, "umask {flag}{shown}")?;
+    } else {
+        writeln!(out, "{shown}")?;
+    }
+    Ok(0)
+}
+
+/// A resource `ulimit` knows: option letter, description, unit and how
+/// many bytes (or other base units) one unit is.
+#[cfg(unix)]
+struct Limit {
+    flag: char,
+    resource: Resource,
+    what: &'static str,
+    unit: &'static str,
+    scale: u64,
+}
+
+#[cfg(unix)]
+const LIMITS: &[Limit] = &[
+    Limit { flag: 'c', resource: Resource::RLIMIT_CORE, what: "core file size", unit: "blocks", scale: 512 },
+    Limit { flag: 'd', resource: Resource::RLIMIT_DATA, what: "data seg size", unit: "kbytes", scale: 1024 },
+    Limit { flag: 'f', resource: Resource::RLIMIT_FSIZE, what: "file size", unit: "blocks", scale: 512 },
+    Limit { flag: 'n', resource: Resource::RLIMIT_NOFILE, what: "open files", unit: "", scale: 1 },
+    Limit { flag: 's', resource: Resource::RLIMIT_STACK, what: "stack size", unit: "kbytes", scale: 1024 },
+    Limit { flag: 't', resource: Resource::RLIMIT_CPU, what: "cpu time", unit: "seconds", scale: 1 },
+    Limit { flag: 'u', resource: Resource::RLIMIT_NPROC, what: "max user processes", unit: "", scale: 1 },
+    Limit { flag: 'v', resource: Resource::RLIMIT_AS, what: "virtual memory", unit: "kbytes", scale: 1024 },
+];
+
+#[cfg(unix)]
+impl Limit {
+    /// `open files                          (-n) ` as `ulimit -a` prints it.
+    fn label(&self) -> String {
+        let unit = if self.unit.is_empty() {
+            format!("(-{}) ", self.flag)
+        } else {
+            format!("({}, -{}) ", self.unit, self.flag)
+        };
+        format!("{:<20} {unit:>20}", self.what)
+    }
+
+    fn show(&self, value: u64) -> String {
+        if value == RLIM_INFINITY {
+            "unlimited".into()
+        } else {
+            (value / self.scale).to_string()
+        }
+    }
+}
+
+/// ulimit [-HSa] [-cdfnstuv] [limit]: show or set resource limits of the
+/// shell, which its children inherit. `-H`/`-S` pick the hard or soft
+/// limit (setting changes both unless one is named); `-f` is the default.
+#[cfg(unix)]
+pub fn builtin_ulimit(args: &[String]) -> Result<i32> {
+    let (mut hard, mut soft, mut all) = (false, false, false);
+    let mut selected: Vec<&Limit> = Vec::new();
+    let mut i = 0;
+    while let Some(a) = args.get(i).filter(|a| a.starts_with('-') && a.len() > 1) {
+        i += 1;
+        if a == "--" {
+            break;
+        }
+        for c in a.chars().skip(1) {
+            match c {
+                'H' => hard = true,
+                'S' => soft = true,
+                'a' => all = true,
+                _ => match LIMITS.iter().find(|l| l.flag == c) {
+                    Some(l) => selected.push(l),
+                    None => {
+                        eprintln!("ulimit: -{c}: invalid option");
+                        eprintln!("ulimit: usage: ulimit [-SHacdfnstuv] [limit]");
+                        return Ok(2);
+                    }
+                },
+            }
+        }
+    }
+
+    let mut out = io::stdout().lock();
+    if all {
+        for l in LIMITS {
+            let (s, h) = getrlimit(l.resource)?;
+            writeln!(out, "{}{}", l.label(), l.show(if hard { h } else { s }))?;
+        }
+        return Ok(0);
+    }
+    if selected.is_empty() {
+        selected.push(&LIMITS[2]);
+    }
+
+    let Some(value) = args.get(i) else {
+        for l in &selected {
+            let (s, h) = getrlimit(l.resource)?;
+            let shown = l.show(if hard && !soft { h } else { s });
+            if selected.len() > 1 {
+                writeln!(out, "{}{shown}", l.label())?;
+            } else {
+                writeln!(out, "{shown}")?;
+            }
+        }
+        return Ok(0);
+    };
+
+    let mut status = 0;
+    for l in &selected {
+        let (cur_soft, cur_hard) = getrlimit(l.resource)?;
+        let new = match value.as_str() {
+            "unlimited" => RLIM_INFINITY,
+            "hard" => cur_hard,
+            "soft" => cur_soft,
+            v => match v.parse::<u64>().ok().and_then(|n| n.checked_mul(l.scale)) {
+                Some(n) => n,
+                None => {
+                    eprintln!("ulimit: {v}: invalid number");
+                    return Ok(1);
+                }
+            },
+        };
+        // Without -H or -S both limits change.
+        let (set_soft, set_hard) = if hard || soft { (soft, hard) } else { (true, true) };
+        let new_soft = if set_soft { new } else { cur_soft };
+        let new_hard = if set_hard { new } else { cur_hard };
+        if let Err(e) = setrlimit(l.resource, new_soft, new_hard) {
+            eprintln!("ulimit: {}: cannot modify limit: {}", l.what, e.desc());
+            status = 1;
+        }
+    }
+    Ok(status)
+}
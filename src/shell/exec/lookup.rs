@@ -0,0 +1,370 @@
+use super::builtins::is_builtin;
+use crate::prelude::*;
+use crate::shell::parser::ast::Command;
+use crate::shell::parser::grammar::RESERVED_WORDS;
+use crate::shell::Shell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// PATH searched by `command -p`.
+pub const DEFAULT_PATH: &str = "/usr/bin:/bin";
+
+/// Remembered PATH lookups (`hash`). The entries belong to the PATH they
+/// were found in: any change to PATH empties the table.
+#[derive(Default)]
+pub struct PathCache {
+    path: String,
+    entries: BTreeMap<String, Hashed>,
+}
+
+struct Hashed {
+    program: PathBuf,
+    hits: u32,
+}
+
+impl PathCache {
+    fn sync(&mut self, path: &str) {
+        if self.path != path {
+            self.entries.clear();
+            self.path = path.to_string();
+        }
+    }
+
+    /// Find `cmd` to run it: the remembered program if it is still there,
+    /// otherwise a new PATH search whose result is remembered.
+    #[cfg(feature = "external")]
+    pub fn find(&mut self, cmd: &str, path: &str) -> Option<PathBuf> {
+        if cmd.contains('/') {
+            return search_path(cmd, path);
+        }
+        self.sync(path);
+        if let Some(h) = self.entries.get_mut(cmd).filter(|h| is_executable(&h.program)) {
+            h.hits += 1;
+            return Some(h.program.clone());
+        }
+        let program = search_path(cmd, path)?;
+        self.entries.insert(cmd.to_string(), Hashed { program: program.clone(), hits: 1 });
+        Some(program)
+    }
+
+    /// The remembered program for `cmd`, if PATH hasn't changed since.
+    fn hashed(&self, cmd: &str, path: &str) -> Option<&Path> {
+        (self.path == path).then(|| self.entries.get(cmd)).flatten().map(|h| h.program.as_path())
+    }
+}
+
+/// Search `path` for an executable `cmd`; a name with a slash is used as is.
+pub fn search_path(cmd: &str, path: &str) -> Option<PathBuf> {
+    search_all(cmd, path).into_iter().next()
+}
+
+/// Every executable `cmd` along `path`, in order.
+fn search_all(cmd: &str, path: &str) -> Vec<PathBuf> {
+    if cmd.contains('/') {
+        let p = PathBuf::from(cmd);
+        return if p.exists() { vec![p] } else { Vec::new() };
+    }
+    path.split(':')
+        .map(|dir| PathBuf::from(if dir.is_empty() { "." } else { dir }).join(cmd))
+        .filter(|cand| is_executable(cand))
+        .collect()
+}
+
+fn is_executable(p: &Path) -> bool {
+    let Ok(md) = std::fs::metadata(p) else { return false };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        md.is_file() && (md.mode() & 0o111) != 0
+    }
+    #[cfg(not(unix))]
+    md.is_file()
+}
+
+/// What a command name refers to, in the order the shell looks.
+enum Found {
+    Alias(String),
+    Keyword,
+    Function(Rc<Command>),
+    Builtin,
+    File { program: PathBuf, hashed: bool },
+}
+
+impl Found {
+    fn kind(&self) -> &'static str {
+        match self {
+            Found::Alias(_) => "alias",
+            Found::Keyword => "keyword",
+            Found::Function(_) => "function",
+            Found::Builtin => "builtin",
+            Found::File { .. } => "file",
+        }
+    }
+
+    /// `type`'s sentence: `ls is /usr/bin/ls`, `cd is a shell builtin`...
+    fn describe(&self, name: &str) -> String {
+        match self {
+            Found::Alias(value) => format!("{name} is aliased to `{value}'"),
+            Found::Keyword => format!("{name} is a shell keyword"),
+            Found::Function(body) => format!("{name} is a function\n{}", Command::FunctionDef {
+                name: name.to_string(),
+                body: body.clone(),
+            }),
+            Found::Builtin => format!("{name} is a shell builtin"),
+            Found::File { program, hashed: true } => format!("{name} is hashed ({})", program.display()),
+            Found::File { program, .. } => format!("{name} is {}", program.display()),
+        }
+    }
+}
+
+/// Look `name` up, searching `path` for files. `all` keeps every match
+/// instead of only the one that would run.
+fn find(shell: &Shell, name: &str, all: bool, path: &str) -> Vec<Found> {
+    let mut found = Vec::new();
+    if let Some(value) = shell.aliases.get(name) {
+        found.push(Found::Alias(value.to_string()));
+    }
+    if RESERVED_WORDS.contains(&name) {
+        found.push(Found::Keyword);
+    }
+    if let Some(body) = shell.functions.get(name) {
+        found.push(Found::Function(body));
+    }
+    if is_builtin(name) {
+        found.push(Found::Builtin);
+    }
+    if !all && !found.is_empty() {
+        found.truncate(1);
+        return found;
+    }
+    match shell.path_cache.hashed(name, path) {
+        Some(program) if !all => found.push(Found::File { program: program.to_path_buf(), hashed: true }),
+        _ => found.extend(search_all(name, path).into_iter().map(|program| Found::File { program, hashed: false })),
+    }
+    if !all {
+        found.truncate(1);
+    }
+    found
+}
+
+fn path_var(shell: &Shell) -> &str {
+    shell.vars.get_str("PATH").unwrap_or_default()
+}
+
+/// type [-aptP] name...
+pub fn builtin_type(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let (mut all, mut kind_only, mut path_only, mut force_path) = (false, false, false, false);
+    let mut i = 0;
+    while let Some(a) = args.get(i).filter(|a| a.starts_with('-') && a.len() > 1) {
+        if a == "--" {
+            i += 1;
+            break;
+        }
+        for c in a.chars().skip(1) {
+            match c {
+                'a' => all = true,
+                't' => kind_only = true,
+                'p' => path_only = true,
+                'P' => force_path = true,
+                _ => {
+                    eprintln!("type: -{c}: invalid option");
+                    eprintln!("type: usage: type [-apt] name [name ...]");
+                    return Ok(2);
+                }
+            }
+        }
+        i += 1;
+    }
+
+    let mut out = io::stdout().lock();
+    let mut status = 0;
+    for name in &args[i..] {
+        let found: Vec<Found> = if force_path {
+            let files = search_all(name, path_var(shell));
+            files.into_iter().take(if all { usize::MAX } else { 1 }).map(|program| Found::File { program, hashed: false }).collect()
+        } else {
+            find(shell, name, all, path_var(shell))
+        };
+        if found.is_empty() {
+            if !kind_only && !path_only && !force_path {
+                eprintln!("type: {name}: not found");
+            }
+            status = 1;
+            continue;
+        }
+        for f in &found {
+            if path_only || force_path {
+                if let Found::File { program, .. } = f {
+                    writeln!(out, "{}", program.display())?;
+                }
+            } else if kind_only {
+                writeln!(out, "{}", f.kind())?;
+            } else {
+                writeln!(out, "{}", f.describe(name))?;
+            }
+        }
+    }
+    Ok(status)
+}
+
+/// which [-a] name...: the program each name runs from PATH (every one
+/// with `-a`). Aliases, functions and builtins are not looked at.
+pub fn builtin_which(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut all = false;
+    let mut i = 0;
+    while let Some(a) = args.get(i).filter(|a| a.starts_with('-') && a.len() > 1) {
+        i += 1;
+        if a == "--" {
+            break;
+        }
+        for c in a.chars().skip(1) {
+            match c {
+                'a' => all = true,
+                _ => {
+                    eprintln!("which: -{c}: invalid option");
+                    eprintln!("which: usage: which [-a] name [name ...]");
+                    return Ok(2);
+                }
+            }
+        }
+    }
+
+    let mut out = io::stdout().lock();
+    let mut status = 0;
+    for name in &args[i..] {
+        let mut files = search_all(name, path_var(shell));
+        files.retain(|p| is_executable(p));
+        if files.is_empty() {
+            status = 1;
+        }
+        for program in files.iter().take(if all { usize::MAX } else { 1 }) {
+            writeln!(out, "{}", program.display())?;
+        }
+    }
+    Ok(status)
+}
+
+/// `command -v` / `command -V`: print what each name would run (`-p`
+/// searches DEFAULT_PATH).
+pub fn describe_command(shell: &Shell, names: &[String], verbose: bool, default_path: bool) -> Result<i32> {
+    let path = if default_path { DEFAULT_PATH } else { path_var(shell) };
+    let mut out = io::stdout().lock();
+    let mut status = 0;
+    for name in names {
+        let Some(found) = find(shell, name, false, path).into_iter().next() else {
+            if verbose {
+                eprintln!("command: {name}: not found");
+            }
+            status = 1;
+            continue;
+        };
+        if verbose {
+            writeln!(out, "{}", found.describe(name))?;
+            continue;
+        }
+        match found {
+            Found::Alias(value) => writeln!(out, "alias {name}={}", crate::shell::util::quote::single_quote(&value))?,
+            Found::File { program, .. } => writeln!(out, "{}", program.display())?,
+            _ => writeln!(out, "{name}")?,
+        }
+    }
+    Ok(status)
+}
+
+/// hash [-lrt] [-d name] [-p path name] [name...]
+pub fn builtin_hash(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let (mut list, mut reset, mut delete, mut show) = (false, false, false, false);
+    let mut set_path: Option<String> = None;
+    let mut i = 0;
+    while let Some(a) = args.get(i).filter(|a| a.starts_with('-') && a.len() > 1) {
+        i += 1;
+        if a == "--" {
+            break;
+        }
+        for c in a.chars().skip(1) {
+            match c {
+                'l' => list = true,
+                'r' => reset = true,
+                'd' => delete = true,
+                't' => show = true,
+                'p' => {
+                    let Some(p) = args.get(i) else {
+                        eprintln!("hash: -p: option requires an argument");
+                        return Ok(2);
+                    };
+                    set_path = Some(p.clone());
+                    i += 1;
+                }
+                _ => {
+                    eprintln!("hash: -{c}: invalid option");
+                    eprintln!("hash: usage: hash [-lr] [-p pathname] [-dt] [name ...]");
+                    return Ok(2);
+                }
+            }
+        }
+    }
+    let names = &args[i..];
+    let path = path_var(shell).to_string();
+    shell.path_cache.sync(&path);
+    if reset {
+        shell.path_cache.entries.clear();
+    }
+
+    if names.is_empty() {
+        if reset || set_path.is_some() || delete || show {
+            return Ok(0);
+        }
+        let cache = &shell.path_cache;
+        let mut out = io::stdout().lock();
+        if cache.entries.is_empty() {
+            writeln!(out, "hash: hash table empty")?;
+            return Ok(0);
+        }
+        if !list {
+            writeln!(out, "hits\tcommand")?;
+        }
+        for (name, h) in &cache.entries {
+            if list {
+                writeln!(out, "builtin hash -p {} {name}", h.program.display())?;
+            } else {
+                writeln!(out, "{:4}\t{}", h.hits, h.program.display())?;
+            }
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    let mut out = io::stdout().lock();
+    for name in names {
+        let entries = &mut shell.path_cache.entries;
+        if let Some(program) = &set_path {
+            entries.insert(name.clone(), Hashed { program: program.into(), hits: 0 });
+        } else if delete {
+            if entries.remove(name).is_none() {
+                eprintln!("hash: {name}: not found");
+                status = 1;
+            }
+        } else if show {
+            match entries.get(name) {
+                Some(h) if names.len() > 1 => writeln!(out, "{name}\t{}", h.program.display())?,
+                Some(h) => writeln!(out, "{}", h.program.display())?,
+                None => {
+                    eprintln!("hash: {name}: not found");
+                    status = 1;
+                }
+            }
+        } else if name.contains('/') || is_builtin(name) || shell.functions.get(name).is_some() {
+            // Nothing to remember.
+        } else {
+            match search_path(name, &path) {
+                Some(program) => { entries.insert(name.clone(), Hashed { program, hits: 0 }); }
+                None => {
+                    eprintln!("hash: {name}: not found");
+                    status = 1;
+                }
+            }
+        }
+    }
+    Ok(status)
+}
@@ -0,0 +1,137 @@
+use super::read::{check_fd, FdInput};
+use crate::prelude::*;
+use crate::shell::scripting::run_script_string;
+use crate::shell::util::quote::single_quote;
+use crate::shell::vars::{is_valid_name, Value};
+use crate::shell::Shell;
+
+const USAGE: &str = "mapfile: usage: mapfile [-d delim] [-n count] [-O origin] [-s count] [-t] [-u fd] [-C callback] [-c quantum] [array]";
+
+struct MapfileOptions {
+    delim: u8,
+    /// Lines to store; 0 is all of them.
+    count: usize,
+    /// First index to store at; without `-O` the array is emptied first.
+    origin: Option<usize>,
+    skip: usize,
+    trim: bool,
+    fd: i32,
+    callback: Option<String>,
+    quantum: usize,
+}
+
+/// mapfile / readarray [-d delim] [-n count] [-O origin] [-s count] [-t]
+/// [-u fd] [-C callback] [-c quantum] [array]: store the lines of the input
+/// in an indexed array (MAPFILE by default). Every `quantum` lines the
+/// callback runs with the next index and that line as arguments.
+pub fn builtin_mapfile(shell: &mut Shell, name: &str, args: &[String]) -> Result<i32> {
+    let mut o = MapfileOptions {
+        delim: b'\n',
+        count: 0,
+        origin: None,
+        skip: 0,
+        trim: false,
+        fd: 0,
+        callback: None,
+        quantum: 5000,
+    };
+    let mut i = 0;
+    while let Some(a) = args.get(i).filter(|a| a.starts_with('-') && a.len() > 1) {
+        i += 1;
+        if a == "--" {
+            break;
+        }
+        for (pos, c) in a.char_indices().skip(1) {
+            if c == 't' {
+                o.trim = true;
+                continue;
+            }
+            if !"dnOsuCc".contains(c) {
+                eprintln!("{name}: -{c}: invalid option");
+                eprintln!("{USAGE}");
+                return Ok(2);
+            }
+            // The value is the rest of this word, or the next argument.
+            let value = match &a[pos + c.len_utf8()..] {
+                "" => match args.get(i) {
+                    Some(v) => {
+                        i += 1;
+                        v.clone()
+                    }
+                    None => {
+                        eprintln!("{name}: -{c}: option requires an argument");
+                        eprintln!("{USAGE}");
+                        return Ok(2);
+                    }
+                },
+                rest => rest.to_string(),
+            };
+            match c {
+                'd' => o.delim = value.bytes().next().unwrap_or(0),
+                'C' => o.callback = Some(value),
+                _ => {
+                    let Ok(n) = value.parse::<usize>() else {
+                        match c {
+                            'u' => eprintln!("{name}: {value}: invalid file descriptor specification"),
+                            'c' => eprintln!("{name}: {value}: invalid callback quantum"),
+                            'O' => eprintln!("{name}: {value}: invalid array origin"),
+                            _ => eprintln!("{name}: {value}: invalid line count"),
+                        }
+                        return Ok(1);
+                    };
+                    match c {
+                        'n' => o.count = n,
+                        'O' => o.origin = Some(n),
+                        's' => o.skip = n,
+                        'u' => o.fd = n as i32,
+                        _ if n == 0 => {
+                            eprintln!("{name}: {value}: invalid callback quantum");
+                            return Ok(1);
+                        }
+                        _ => o.quantum = n,
+                    }
+                }
+            }
+            break;
+        }
+    }
+    let array = args.get(i).map_or("MAPFILE", String::as_str);
+    if !is_valid_name(array) {
+        eprintln!("{name}: `{array}': not a valid identifier");
+        return Ok(1);
+    }
+    if !check_fd(name, o.fd) {
+        return Ok(1);
+    }
+
+    if o.origin.is_none() || !shell.vars.get(array).is_some_and(|v| v.value.is_array()) {
+        shell.vars.unset(array);
+        shell.vars.set_value(array, Value::Indexed(Default::default()));
+    }
+    let mut index = o.origin.unwrap_or(0);
+    let mut input = FdInput::new(o.fd);
+    let mut stored = 0;
+    let mut skipped = 0;
+    while o.count == 0 || stored < o.count {
+        let Some((mut line, found)) = input.read_until(o.delim)? else { break };
+        if skipped < o.skip {
+            skipped += 1;
+            continue;
+        }
+        if found && !o.trim {
+            line.push(o.delim);
+        }
+        let line = String::from_utf8_lossy(&line).into_owned();
+        stored += 1;
+        if let Some(callback) = o.callback.as_ref().filter(|_| stored % o.quantum == 0) {
+            let cmd = format!("{callback} {index} {}", single_quote(&line));
+            run_script_string(shell, name, &cmd)?;
+        }
+        if let Err(e) = shell.vars.set_element(array, &index.to_string(), line) {
+            eprintln!("{name}: {e}");
+            return Ok(1);
+        }
+        index += 1;
+    }
+    Ok(0)
+}
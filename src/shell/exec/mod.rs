@@ -1,47 +1,416 @@
 pub mod builtins;
 pub mod status;
 pub mod fileops;
-pub mod env;
+pub mod expand;
+pub mod assign;
+pub mod declare;
+pub mod redirect;
+pub mod compound;
+pub mod printf;
+pub mod lookup;
+pub mod read;
+pub mod mapfile;
+pub mod limits;
+pub mod procsub;
 
 use crate::prelude::*;
-use crate::shell::parser::ParsedCommand;
-
-pub use builtins::dispatch_builtin;
+use crate::shell::parser::ast::{AndOr, Command, CompoundCommand, Connector, List, Pipeline, SimpleCommand, TimeFormat};
+use crate::shell::signals::trap::{self, Trap};
+use crate::shell::Shell;
+use crate::shell::util::quote::quote_if_needed;
+use crate::shell::util::time::{format_times, CpuTimes, DEFAULT_TIMEFORMAT, POSIX_TIMEFORMAT};
+use redirect::with_redirects;
+use status::Flow;
+use std::rc::Rc;
+use std::time::Instant;
 
 #[cfg(unix)]
 use nix::{
     sys::wait::{waitpid, WaitPidFlag, WaitStatus},
-    unistd::{execve, fork, getpid, setpgid, ForkResult, Pid},
+    unistd::{close, dup2, fork, getpid, pipe, setpgid, ForkResult, Pid},
 };
 #[cfg(unix)]
-use std::{ffi::CString, os::unix::ffi::OsStrExt, path::PathBuf};
+use std::os::fd::{AsRawFd, RawFd};
+#[cfg(all(unix, feature = "external"))]
+use crate::shell::scripting::runner;
+#[cfg(all(unix, feature = "external"))]
+use std::{ffi::CString, os::unix::ffi::OsStrExt};
 
-/// Run one parsed command (maybe background). Returns exit status.
-/// If the builtin was `exit`, return EXIT_SIGNAL so caller can break the REPL.
-pub fn run_parsed_command(shell: &mut crate::shell::Shell, p: ParsedCommand) -> Result<i32> {
-    if builtins::is_builtin(&p.cmd) {
-        return builtins::dispatch_builtin(shell, p.cmd, &p.args);
+/// Run a parsed list. Returns the status of the last command; `exit`,
+/// `return`, `break` and `continue` come back as a `Flow` error.
+pub fn run_list(shell: &mut Shell, list: &List) -> Result<i32> {
+    let mut status = 0;
+    for item in list {
+        status = if item.background { run_background(shell, item)? } else { run_and_or(shell, item)? };
+        trap::run_pending(shell)?;
     }
+    Ok(status)
+}
 
-    // External programs:
-    // - Enabled only on Unix AND when the "external" feature is set.
-    // - Otherwise, refuse to run (to satisfy the "no external binaries" audit).
-    #[cfg(all(unix, feature = "external"))]
-    {
-        return run_external(shell, &p.cmd, &p.args, p.background);
+/// `a && b || c`; `$?` is updated after every pipeline.
+fn run_and_or(shell: &mut Shell, item: &AndOr) -> Result<i32> {
+    // Only the final pipeline of a chain can trigger `set -e`.
+    let mut last = &item.first;
+    let mut status = if item.rest.is_empty() {
+        run_pipeline(shell, &item.first)?
+    } else {
+        without_errexit(shell, |sh| run_pipeline(sh, &item.first))?
+    };
+    let mut from_final = item.rest.is_empty();
+    for (i, (conn, next)) in item.rest.iter().enumerate() {
+        shell.last_status = status;
+        let run = match conn {
+            Connector::And => status == 0,
+            Connector::Or => status != 0,
+        };
+        if run {
+            from_final = i + 1 == item.rest.len();
+            last = next;
+            status = if from_final { run_pipeline(shell, next)? } else { without_errexit(shell, |sh| run_pipeline(sh, next))? };
+        }
+    }
+    shell.last_status = status;
+    // ERR trap first, then `set -e`.
+    if status != 0 && from_final && failure_counts(shell, last) {
+        trap::run(shell, Trap::Err)?;
+        if shell.options.get("errexit") {
+            return Err(Flow::Exit(status).into());
+        }
     }
+    Ok(status)
+}
 
-    #[cfg(not(all(unix, feature = "external")))]
-    {
-        eprintln!("Command '{}' not found", p.cmd);
-        Ok(127)
+/// Run `f` where a failure must not trigger `set -e`: `if`/`while`
+/// conditions, `!` pipelines and all but the last part of `&&`/`||`.
+pub fn without_errexit<T>(shell: &mut Shell, f: impl FnOnce(&mut Shell) -> T) -> T {
+    shell.errexit_suppressed += 1;
+    let r = f(shell);
+    shell.errexit_suppressed -= 1;
+    r
+}
+
+/// Does a failure of `p` fire the ERR trap and `set -e`? Compound commands
+/// only report the status of what ran inside them, which was already checked.
+fn failure_counts(shell: &Shell, p: &Pipeline) -> bool {
+    shell.errexit_suppressed == 0
+        && !p.negated
+        && matches!(p.commands.last(), Some(Command::Simple(_)) | Some(Command::Compound(CompoundCommand::Subshell(_), _)))
+}
+
+fn run_pipeline(shell: &mut Shell, p: &Pipeline) -> Result<i32> {
+    let Some(format) = p.time else { return run_untimed(shell, p) };
+    let start = Instant::now();
+    let before = cpu_used(shell);
+    let status = run_untimed(shell, p)?;
+    let cpu = cpu_used(shell).since(before);
+    let format = match format {
+        TimeFormat::Posix => POSIX_TIMEFORMAT,
+        TimeFormat::Default => shell.vars.get_str("TIMEFORMAT").unwrap_or(DEFAULT_TIMEFORMAT),
+    };
+    if !format.is_empty() {
+        let _ = io::stdout().flush();
+        eprintln!("{}", format_times(format, start.elapsed(), cpu));
+    }
+    Ok(status)
+}
+
+/// CPU time of the shell plus the foreground children it has waited for.
+fn cpu_used(shell: &Shell) -> CpuTimes {
+    let mut t = CpuTimes::of_self();
+    t.add(shell.child_times);
+    t
+}
+
+fn run_untimed(shell: &mut Shell, p: &Pipeline) -> Result<i32> {
+    let run = |sh: &mut Shell| match p.commands.as_slice() {
+        [] => Ok(0),
+        [single] => run_command(sh, single),
+        _ => run_multi(sh, p),
+    };
+    if !p.negated {
+        return run(shell);
     }
+    let status = without_errexit(shell, run)?;
+    Ok((status == 0) as i32)
 }
 
+/// Run one command in the current shell (externals and subshells still fork).
+pub fn run_command(shell: &mut Shell, cmd: &Command) -> Result<i32> {
+    match cmd {
+        Command::Simple(simple) if procsub::any_in(&simple.words, &simple.redirects) => {
+            let mark = shell.proc_sub_fds.len();
+            let status = procsub::substitute_simple(shell, simple).and_then(|simple| run_simple(shell, &simple));
+            procsub::close_from(shell, mark);
+            status
+        }
+        Command::Simple(simple) => run_simple(shell, simple),
+        Command::Compound(c, redirects) if procsub::any_in(&[], redirects) => {
+            let mark = shell.proc_sub_fds.len();
+            let status = procsub::substitute_redirects(shell, redirects)
+                .and_then(|redirects| with_redirects(shell, &redirects, |sh| compound::run_compound(sh, c)));
+            procsub::close_from(shell, mark);
+            status
+        }
+        Command::Compound(c, redirects) => with_redirects(shell, redirects, |sh| compound::run_compound(sh, c)),
+        Command::FunctionDef { name, body } => {
+            shell.functions.define(name.clone(), Rc::clone(body));
+            Ok(0)
+        }
+        Command::Coproc { name, body } => run_coproc(shell, name, body),
+    }
+}
+
+fn run_simple(shell: &mut Shell, p: &SimpleCommand) -> Result<i32> {
+    // Only the command a forked child was created for may replace it.
+    let exec_in_place = std::mem::take(&mut shell.exec_in_place);
+
+    if shell.traps.get(Trap::Debug).is_some() {
+        shell.vars.set_scalar("BASH_COMMAND", p.to_string());
+        trap::run(shell, Trap::Debug)?;
+    }
+
+    let words = match p.words.split_first() {
+        Some((cmd, args)) if builtins::is_declaration_builtin(cmd) => expand_declaration_args(shell, cmd, args),
+        _ => expand::expand_words(shell, &p.words),
+    };
+    let mut words = match words {
+        Ok(w) => w,
+        Err(e) => return expansion_failed(shell, &e),
+    };
+    if shell.options.get("xtrace") {
+        trace(shell, &p.assigns, &words);
+    }
+
+    // Plain `NAME=value ...` with no command sets shell variables.
+    if words.is_empty() {
+        for a in &p.assigns {
+            if let Err(e) = assign::apply_assignment(shell, a) {
+                eprintln!("0-shell: {e}");
+                return Ok(1);
+            }
+        }
+        return with_redirects(shell, &p.redirects, |_| Ok(0));
+    }
+
+    // `NAME=value cmd` only affects that command: apply, export, run, restore.
+    let mut saved = Vec::with_capacity(p.assigns.len());
+    for a in &p.assigns {
+        let Some((lhs, _)) = assign::parse_lhs(a) else { continue };
+        let name = lhs.name.to_string();
+        saved.push((name.clone(), shell.vars.get(&name).cloned()));
+        if let Err(e) = assign::apply_assignment(shell, a) {
+            eprintln!("0-shell: {e}");
+            for (name, var) in saved.into_iter().rev() { shell.vars.restore(&name, var); }
+            return Ok(1);
+        }
+        shell.vars.set_exported(&name, true);
+    }
+
+    let cmd = words.remove(0);
+    let status = run_named(shell, cmd, words, p, exec_in_place, Resolve::Any);
+
+    for (name, var) in saved.into_iter().rev() {
+        shell.vars.restore(&name, var);
+    }
+    status
+}
+
+/// What a command name may resolve to: `command` skips functions and
+/// `builtin` only runs builtins.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Resolve {
+    Any,
+    NoFunctions,
+    BuiltinOnly,
+}
+
+/// Run the command `cmd args...` after expansion and assignments.
+fn run_named(shell: &mut Shell, cmd: String, args: Vec<String>, p: &SimpleCommand, exec_in_place: bool, resolve: Resolve) -> Result<i32> {
+    match cmd.as_str() {
+        "exec" => return run_exec(shell, &args, p),
+        "command" => return run_command_builtin(shell, args, p, exec_in_place),
+        "builtin" => {
+            let mut args = args;
+            if args.first().is_some_and(|a| a == "--") {
+                args.remove(0);
+            }
+            if args.is_empty() {
+                return Ok(0);
+            }
+            let cmd = args.remove(0);
+            return run_named(shell, cmd, args, p, exec_in_place, Resolve::BuiltinOnly);
+        }
+        _ => {}
+    }
+    if resolve == Resolve::Any {
+        if let Some(body) = shell.functions.get(&cmd) {
+            return with_redirects(shell, &p.redirects, |sh| call_function(sh, &body, args));
+        }
+    }
+    if builtins::is_builtin(&cmd) {
+        return with_redirects(shell, &p.redirects, |sh| builtins::run_builtin(sh, cmd, &args));
+    }
+    if resolve == Resolve::BuiltinOnly {
+        eprintln!("builtin: {cmd}: not a shell builtin");
+        return Ok(1);
+    }
+    run_external(shell, &cmd, &args, p, exec_in_place)
+}
+
+/// `command [-pVv] cmd [args...]`: run `cmd` ignoring shell functions, or
+/// with `-v`/`-V` describe it.
+fn run_command_builtin(shell: &mut Shell, args: Vec<String>, p: &SimpleCommand, exec_in_place: bool) -> Result<i32> {
+    let (mut default_path, mut describe) = (false, None);
+    let mut i = 0;
+    while let Some(a) = args.get(i).filter(|a| a.starts_with('-') && a.len() > 1) {
+        i += 1;
+        if a == "--" {
+            break;
+        }
+        for c in a.chars().skip(1) {
+            match c {
+                'p' => default_path = true,
+                'v' => describe = describe.or(Some(false)),
+                'V' => describe = Some(true),
+                _ => {
+                    eprintln!("command: -{c}: invalid option");
+                    eprintln!("command: usage: command [-pVv] command [arg ...]");
+                    return Ok(2);
+                }
+            }
+        }
+    }
+    let mut args = args;
+    args.drain(..i);
+    if args.is_empty() {
+        return Ok(0);
+    }
+    if let Some(verbose) = describe {
+        return with_redirects(shell, &p.redirects, |sh| lookup::describe_command(sh, &args, verbose, default_path));
+    }
+    let mut cmd = args.remove(0);
+    // `-p` finds programs along the standard PATH without changing $PATH.
+    if default_path && !cmd.contains('/') && !builtins::is_builtin(&cmd) {
+        if let Some(program) = lookup::search_path(&cmd, lookup::DEFAULT_PATH) {
+            cmd = program.to_string_lossy().into_owned();
+        }
+    }
+    run_named(shell, cmd, args, p, exec_in_place, Resolve::NoFunctions)
+}
+
+/// `exec [cmd args...]`: replace the shell with `cmd`; without a command,
+/// apply the redirections to the shell for good.
+fn run_exec(shell: &mut Shell, args: &[String], p: &SimpleCommand) -> Result<i32> {
+    let args = match args.first() {
+        Some(a) if a == "--" => &args[1..],
+        _ => args,
+    };
+    let Some((cmd, rest)) = args.split_first() else {
+        #[cfg(unix)]
+        if let Err(e) = redirect::apply(shell, &p.redirects, false) {
+            eprintln!("0-shell: {e}");
+            return Ok(1);
+        }
+        #[cfg(not(unix))]
+        return with_redirects(shell, &p.redirects, |_| Ok(0));
+        return Ok(0);
+    };
+    // The new program starts with default job-control signals.
+    #[cfg(unix)]
+    if shell.interactive {
+        crate::shell::signals::reset_for_child();
+        shell.traps.reapply_ignored();
+    }
+    let status = run_external(shell, cmd, rest, p, true)?;
+    // Still here: the program couldn't be run.
+    if shell.interactive {
+        #[cfg(unix)]
+        crate::shell::signals::install_handlers();
+        return Ok(status);
+    }
+    Err(Flow::Exit(status).into())
+}
+
+/// Report an expansion error. Returns the command's status, or exits a
+/// non-interactive shell with status 127 for fatal errors (`set -u`).
+pub fn expansion_failed(shell: &mut Shell, err: &expand::ExpandError) -> Result<i32> {
+    eprintln!("0-shell: {err}");
+    if matches!(err, expand::ExpandError::Unbound(_)) && !shell.interactive {
+        return Err(Flow::Exit(127).into());
+    }
+    Ok(1)
+}
+
+/// `set -x`: print the command about to run after `PS4`.
+fn trace(shell: &Shell, assigns: &[String], words: &[String]) {
+    let ps4 = shell.vars.get_str("PS4").unwrap_or("+ ");
+    let mut line = expand::expand_word_single(shell, ps4).unwrap_or_else(|_| ps4.to_string());
+    let mut items = Vec::with_capacity(assigns.len() + words.len());
+    for a in assigns {
+        match assign::parse_lhs(a) {
+            Some((_, rhs)) if !rhs.starts_with('(') => {
+                let value = expand::expand_word_single(shell, rhs).unwrap_or_default();
+                items.push(format!("{}{}", &a[..a.len() - rhs.len()], quote_if_needed(&value)));
+            }
+            _ => items.push(a.clone()),
+        }
+    }
+    items.extend(words.iter().map(|w| quote_if_needed(w)));
+    line.push_str(&items.join(" "));
+    let _ = io::stdout().flush();
+    eprintln!("{line}");
+}
+
+/// Run a function body with `args` as the positional parameters.
+fn call_function(shell: &mut Shell, body: &Command, args: Vec<String>) -> Result<i32> {
+    let saved = std::mem::replace(&mut shell.positional, args);
+    // Loops of the caller can't be left with `break` from inside the function.
+    let loops = std::mem::take(&mut shell.loop_depth);
+    shell.return_depth += 1;
+    let status = run_command(shell, body);
+    shell.return_depth -= 1;
+    shell.loop_depth = loops;
+    shell.positional = saved;
+    let status = catch_return(status)?;
+    shell.last_status = status;
+    trap::run(shell, Trap::Return)?;
+    Ok(status)
+}
+
+/// The status of a function or sourced file: `return N` ends it with N.
+pub fn catch_return(result: Result<i32>) -> Result<i32> {
+    match result {
+        Err(e) => match Flow::of(&e) {
+            Some(Flow::Return(n)) => Ok(n),
+            _ => Err(e),
+        },
+        ok => ok,
+    }
+}
+
+/// declare/export take `name=value` operands like assignments: no field
+/// splitting of the value, and `name=(...)` is left for the builtin to expand.
+fn expand_declaration_args(shell: &Shell, cmd: &str, args: &[String]) -> std::result::Result<Vec<String>, expand::ExpandError> {
+    let mut out = vec![cmd.to_string()];
+    for a in args {
+        match assign::parse_lhs(a) {
+            Some((_, rhs)) if crate::shell::parser::grammar::is_assignment(a) => {
+                let lhs = &a[..a.len() - rhs.len()];
+                if rhs.starts_with('(') && rhs.ends_with(')') {
+                    out.push(a.clone());
+                } else {
+                    out.push(format!("{lhs}{}", expand::expand_word_single(shell, rhs)?));
+                }
+            }
+            _ => out.extend(expand::expand_word(shell, a)?),
+        }
+    }
+    Ok(out)
+}
 
 /// Opportunistic reaper to keep job table fresh (Unix).
 /// Returns true if any child state changed (so the caller can redraw the prompt on a fresh line).
-pub fn maybe_reap(shell: &mut crate::shell::Shell) -> bool {
+pub fn maybe_reap(shell: &mut Shell) -> bool {
     let mut changed = false;
     #[cfg(unix)]
     {
@@ -65,114 +434,332 @@ pub fn maybe_reap(shell: &mut crate::shell::Shell) -> bool {
     changed
 }
 
-/// Search PATH for program (Unix)
+/// Run an external program in a child (or in place, inside a pipeline
+/// child) and wait for it.
+#[cfg(all(unix, feature = "external"))]
+fn run_external(shell: &mut Shell, cmd: &str, args: &[String], p: &SimpleCommand, exec_in_place: bool) -> Result<i32> {
+    let Some(program) = shell.path_cache.find(cmd, shell.vars.get_str("PATH").unwrap_or_default()) else {
+        eprintln!("Command '{}' not found", cmd);
+        return Ok(127);
+    };
+
+    // Build argv/envp for execve before forking
+    let path = CString::new(program.as_os_str().as_bytes().to_vec()).unwrap();
+    let mut argv: Vec<CString> = Vec::with_capacity(args.len() + 1);
+    argv.push(path.clone());
+    for a in args {
+        argv.push(CString::new(a.as_str()).unwrap());
+    }
+    let envp: Vec<CString> = shell.vars.exported_env()
+        .into_iter()
+        .map(|(k, v)| CString::new(format!("{k}={v}")).unwrap())
+        .collect();
+
+    let exec = move |sh: &mut Shell| -> Result<i32> {
+        if let Err(e) = redirect::apply(sh, &p.redirects, false) {
+            eprintln!("0-shell: {e}");
+            return Ok(1);
+        }
+        // Scripts for this shell, and text files the kernel won't run
+        // (no `#!`), are run by a new shell in this process instead.
+        let run_here = |sh: &Shell| -> ! {
+            std::process::exit(runner::run_in_fresh_shell(sh, &program, cmd, args))
+        };
+        if runner::has_own_shebang(&program) {
+            run_here(sh);
+        }
+        crate::shell::signals::sigpipe_for_exec(&sh.traps);
+        let err = nix::unistd::execve(&path, &argv, &envp).unwrap_err();
+        crate::shell::signals::sigpipe_for_shell();
+        if err == nix::errno::Errno::ENOEXEC {
+            run_here(sh);
+        }
+        eprintln!("exec: {}: {}", cmd, err);
+        Ok(if err == nix::errno::Errno::ENOENT { 127 } else { 126 })
+    };
+    if exec_in_place {
+        return exec(shell);
+    }
+    let text = p.to_string();
+    run_foreground_job(shell, &text, exec)
+}
+
+#[cfg(not(all(unix, feature = "external")))]
+fn run_external(_shell: &mut Shell, cmd: &str, _args: &[String], _p: &SimpleCommand, _exec_in_place: bool) -> Result<i32> {
+    // External programs:
+    // - Enabled only on Unix AND when the "external" feature is set.
+    // - Otherwise, refuse to run (to satisfy the "no external binaries" audit).
+    eprintln!("Command '{}' not found", cmd);
+    Ok(127)
+}
+
+/// `( list )`: run in a forked copy of the shell so changes don't leak out.
+pub fn run_subshell(shell: &mut Shell, list: &List) -> Result<i32> {
+    #[cfg(unix)]
+    {
+        let text = CompoundCommand::Subshell(list.clone()).to_string();
+        run_foreground_job(shell, &text, |sh| run_list(sh, list))
+    }
+    #[cfg(not(unix))]
+    {
+        run_list(shell, list)
+    }
+}
+
+/// Fork one child running `f` as a foreground job and wait for it.
 #[cfg(unix)]
-fn which(cmd: &str) -> Option<PathBuf> {
-    use std::{env, fs};
-    if cmd.contains('/') {
-        let p = PathBuf::from(cmd);
-        if p.exists() { return Some(p); }
-        return None;
-    }
-    let path = env::var("PATH").ok()?;
-    for dir in path.split(':') {
-        let cand = PathBuf::from(dir).join(cmd);
-        if let Ok(md) = fs::metadata(&cand) {
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::MetadataExt;
-                if md.is_file() && (md.mode() & 0o111) != 0 {
-                    return Some(cand);
-                }
+fn run_foreground_job(shell: &mut Shell, text: &str, f: impl FnOnce(&mut Shell) -> Result<i32>) -> Result<i32> {
+    use crate::shell::jobs::JobState;
+    let child = fork_child(shell, None, true, (None, None), &[], f)?;
+    if shell.interactive {
+        let _ = setpgid(child, child);
+    }
+    shell.jobs.add_job(child, JobState::Running, text.to_string(), Vec::new());
+    wait_foreground(shell, child, &[child])
+}
+
+/// `a | b | c`: one child per command, all in one process group.
+fn run_multi(shell: &mut Shell, p: &Pipeline) -> Result<i32> {
+    #[cfg(unix)]
+    {
+        use crate::shell::jobs::JobState;
+        let mut pids: Vec<Pid> = Vec::with_capacity(p.commands.len());
+        let mut prev_read = None;
+        for (i, cmd) in p.commands.iter().enumerate() {
+            let last = i + 1 == p.commands.len();
+            let next = if last { None } else { Some(cloexec_pipe()?) };
+            let stdin = prev_read.as_ref().map(|fd: &std::os::fd::OwnedFd| fd.as_raw_fd());
+            let stdout = next.as_ref().map(|(_, w)| w.as_raw_fd());
+            let mut close_fds: Vec<RawFd> = stdin.into_iter().chain(stdout).collect();
+            if let Some((r, _)) = &next {
+                close_fds.push(r.as_raw_fd());
+            }
+            let pgid = pids.first().copied();
+            let child = fork_child(shell, pgid, true, (stdin, stdout), &close_fds, |sh| {
+                sh.exec_in_place = matches!(cmd, Command::Simple(_));
+                run_command(sh, cmd)
+            })?;
+            if shell.interactive {
+                let _ = setpgid(child, pgid.unwrap_or(child));
             }
+            pids.push(child);
+            prev_read = next.map(|(r, _w)| r);
+        }
+        drop(prev_read);
+        let pgid = pids[0];
+        let mut text = p.to_string();
+        if let Some(rest) = text.strip_prefix("! ") {
+            text = rest.to_string();
         }
+        let id = shell.jobs.add_job(pgid, JobState::Running, text, Vec::new());
+        if let Some(job) = shell.jobs.by_id(id) {
+            job.pids = pids.iter().map(|p| p.as_raw()).collect();
+        }
+        wait_foreground(shell, pgid, &pids)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (shell, p);
+        eprintln!("0-shell: pipelines are not supported on this platform");
+        Ok(1)
     }
-    None
 }
 
-/// Exec external with job-control semantics (Unix)
+/// A pipe whose ends aren't inherited by programs we exec (children dup2
+/// them onto 0/1, which clears the flag on the copies).
 #[cfg(unix)]
-fn run_external(shell: &mut crate::shell::Shell, cmd: &str, args: &[String], background: bool) -> Result<i32> {
-    use crate::shell::jobs::JobState;
-    use crate::shell::signals::tty;
+fn cloexec_pipe() -> Result<(std::os::fd::OwnedFd, std::os::fd::OwnedFd)> {
+    use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+    let (r, w) = pipe()?;
+    for fd in [&r, &w] {
+        fcntl(fd.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+    }
+    Ok((r, w))
+}
+
+/// `list &`: run the and-or list in a child and register it as a job.
+fn run_background(shell: &mut Shell, item: &AndOr) -> Result<i32> {
+    #[cfg(unix)]
+    {
+        use crate::shell::jobs::JobState;
+        let child = fork_child(shell, None, false, (None, None), &[], |sh| {
+            let fg = AndOr { background: false, ..item.clone() };
+            if fg.rest.is_empty() && fg.first.commands.len() == 1 && !fg.first.negated {
+                sh.exec_in_place = matches!(fg.first.commands[0], Command::Simple(_));
+            }
+            run_and_or(sh, &fg)
+        })?;
+        if shell.interactive {
+            let _ = setpgid(child, child);
+        }
+        let text = AndOr { background: false, ..item.clone() }.to_string();
+        let id = shell.jobs.add_job(child, JobState::Running, text, Vec::new());
+        if shell.interactive {
+            println!("[{}] {}", id, child.as_raw());
+        }
+        Ok(0)
+    }
+    #[cfg(not(unix))]
+    {
+        run_and_or(shell, item)
+    }
+}
 
-    let program = match which(cmd) {
-        Some(p) => p,
-        None => {
-            eprintln!("Command '{}' not found", cmd);
-            return Ok(127);
+/// `coproc NAME cmd`: start `cmd` as a background job with a pipe to its
+/// stdin in `NAME[1]` and one from its stdout in `NAME[0]`; `NAME_PID` is its pid.
+fn run_coproc(shell: &mut Shell, name: &str, body: &Command) -> Result<i32> {
+    #[cfg(unix)]
+    {
+        use crate::shell::jobs::JobState;
+        use crate::shell::vars::Value;
+        use std::os::fd::IntoRawFd;
+        // Both pipes are CLOEXEC, so later commands don't hold them open.
+        let (to_child_r, to_child_w) = cloexec_pipe()?;
+        let (from_child_r, from_child_w) = cloexec_pipe()?;
+        let child_fds = (Some(to_child_r.as_raw_fd()), Some(from_child_w.as_raw_fd()));
+        let close_fds = [to_child_r.as_raw_fd(), to_child_w.as_raw_fd(), from_child_r.as_raw_fd(), from_child_w.as_raw_fd()];
+        let child = fork_child(shell, None, false, child_fds, &close_fds, |sh| {
+            sh.exec_in_place = matches!(body, Command::Simple(_));
+            run_command(sh, body)
+        })?;
+        if shell.interactive {
+            let _ = setpgid(child, child);
         }
-    };
+        drop((to_child_r, from_child_w));
 
-    // Build argv for execve
-    let mut argv: Vec<CString> = Vec::with_capacity(args.len() + 1);
-    argv.push(CString::new(program.as_os_str().as_bytes().to_vec()).unwrap());
-    for a in args {
-        argv.push(CString::new(a.as_str()).unwrap());
+        let fds = [from_child_r.into_raw_fd(), to_child_w.into_raw_fd()];
+        let value = Value::Indexed(fds.iter().enumerate().map(|(i, fd)| (i, fd.to_string())).collect());
+        shell.vars.set_value(name, value);
+        shell.vars.set_scalar(&format!("{name}_PID"), child.as_raw().to_string());
+
+        let text = format!("coproc {name} {body}");
+        let id = shell.jobs.add_job(child, JobState::Running, text, Vec::new());
+        if shell.interactive {
+            println!("[{}] {}", id, child.as_raw());
+        }
+        Ok(0)
     }
-    let envp: Vec<CString> = std::env::vars()
-        .map(|(k, v)| CString::new(format!("{k}={v}")).unwrap())
-        .collect();
+    #[cfg(not(unix))]
+    {
+        let _ = (shell, name, body);
+        eprintln!("0-shell: coproc is not supported on this platform");
+        Ok(1)
+    }
+}
+
+/// Fork a child that runs `f` and exits with its status. `pgid` is the
+/// process group to join (None: start a new one) when job control is on;
+/// `stdio` is (stdin, stdout) to install, and `close_fds` are closed in the child.
+#[cfg(unix)]
+fn fork_child(
+    shell: &mut Shell,
+    pgid: Option<Pid>,
+    foreground: bool,
+    stdio: (Option<RawFd>, Option<RawFd>),
+    close_fds: &[RawFd],
+    f: impl FnOnce(&mut Shell) -> Result<i32>,
+) -> Result<Pid> {
+    use crate::shell::signals::{self, tty};
+
+    // Don't let the child inherit (and print again) our buffered output.
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
 
     match unsafe { fork() }? {
+        ForkResult::Parent { child } => Ok(child),
         ForkResult::Child => {
-            let pid = getpid();
-            let _ = setpgid(pid, pid);
-            if !background {
-                let _ = tty::give_terminal_to(pid);
+            if shell.interactive {
+                let pid = getpid();
+                let _ = setpgid(pid, pgid.unwrap_or(pid));
+                if foreground && pgid.is_none() {
+                    let _ = tty::give_terminal_to(pid);
+                }
+                signals::reset_for_child();
+                shell.interactive = false;
             }
-            match execve(&CString::new(program.as_os_str().as_bytes().to_vec()).unwrap(), &argv, &envp) {
-                Ok(_) => unreachable!(),
+            shell.traps.reset_for_subshell();
+            shell.jobs = Default::default();
+            if let Some(fd) = stdio.0 { let _ = dup2(fd, 0); }
+            if let Some(fd) = stdio.1 { let _ = dup2(fd, 1); }
+            for &fd in close_fds {
+                let _ = close(fd);
+            }
+            let last = shell.last_status;
+            let mut status = match status::exit_status(f(shell), last) {
+                Ok(s) => s,
                 Err(e) => {
-                    eprintln!("exec: {}: {}", cmd, e);
-                    std::process::exit(127);
+                    eprintln!("0-shell: {e}");
+                    1
                 }
+            };
+            // An EXIT trap set inside the subshell.
+            shell.last_status = status;
+            if let Ok(s) = status::exit_status(trap::run_exit(shell).map(|_| status), status) {
+                status = s;
             }
+            let _ = io::stdout().flush();
+            std::process::exit(status);
         }
-        ForkResult::Parent { child } => {
-            let _ = setpgid(child, child);
-            let id = shell.jobs.add_job(child, JobState::Running, cmd.to_string(), args.to_vec());
-
-            if background {
-                println!("[{}] {}", id, child.as_raw());
-                Ok(0)
-            } else {
-                let _ = tty::give_terminal_to(child);
-                let status = wait_foreground(shell, child);
-                let _ = tty::give_terminal_back_to_shell();
-                status
-            }
-        }
     }
 }
 
+/// `waitpid` through `wait4`, adding the CPU time of a child that ended to
+/// `shell.child_times` (for `time`).
+#[cfg(unix)]
+fn wait_and_account(shell: &mut Shell, pid: Pid, flags: WaitPidFlag) -> nix::Result<WaitStatus> {
+    let mut status = 0;
+    // SAFETY: rusage is plain data; wait4 fills it in.
+    let mut usage: nix::libc::rusage = unsafe { std::mem::zeroed() };
+    let res = unsafe { nix::libc::wait4(pid.as_raw(), &mut status, flags.bits(), &mut usage) };
+    let pid = nix::errno::Errno::result(res)?;
+    let ws = WaitStatus::from_raw(Pid::from_raw(pid), status)?;
+    if matches!(ws, WaitStatus::Exited(..) | WaitStatus::Signaled(..)) {
+        shell.child_times.add(CpuTimes::from_rusage(&usage));
+    }
+    Ok(ws)
+}
+
+/// Wait for the processes of a foreground job. Returns the status of the
+/// last one (128+N if it was killed by signal N).
 #[cfg(unix)]
-fn wait_foreground(shell: &mut crate::shell::Shell, pgid: Pid) -> Result<i32> {
+fn wait_foreground(shell: &mut Shell, pgid: Pid, pids: &[Pid]) -> Result<i32> {
     use crate::shell::jobs::UpdateKind;
-    use nix::sys::wait::WaitStatus;
+    use crate::shell::signals::tty;
 
-    loop {
-        match waitpid(Pid::from_raw(-pgid.as_raw()), Some(WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED)) {
-            Ok(WaitStatus::Exited(_, code)) => {
-                shell.jobs.apply_update(UpdateKind::Terminated { pgid, code });
-                return Ok(code);
-            }
-            Ok(WaitStatus::Signaled(_, _sig, _core)) => {
-                shell.jobs.apply_update(UpdateKind::Terminated { pgid, code: 128 });
-                return Ok(128);
-            }
-            Ok(WaitStatus::Stopped(_, _sig)) => {
-                shell.jobs.apply_update(UpdateKind::Stopped { pgid });
-                return Ok(0);
-            }
-            Ok(WaitStatus::Continued(_)) => {
-                shell.jobs.apply_update(UpdateKind::Running { pgid });
-            }
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("wait: {e}");
-                return Ok(1);
+    if shell.interactive {
+        let _ = tty::give_terminal_to(pgid);
+    }
+    // `set -o pipefail`: the last non-zero status wins.
+    let pipefail = shell.options.get("pipefail");
+    let mut status = 0;
+    for (i, &pid) in pids.iter().enumerate() {
+        let last = i + 1 == pids.len();
+        let code = loop {
+            match wait_and_account(shell, pid, WaitPidFlag::WUNTRACED) {
+                Ok(WaitStatus::Exited(_, code)) => break code,
+                Ok(WaitStatus::Signaled(_, sig, _core)) => break 128 + sig as i32,
+                Ok(WaitStatus::Stopped(_, sig)) => {
+                    shell.jobs.apply_update(UpdateKind::Stopped { pgid });
+                    if shell.interactive {
+                        let _ = tty::give_terminal_back_to_shell();
+                    }
+                    return Ok(128 + sig as i32);
+                }
+                Ok(_) => {}
+                Err(nix::errno::Errno::EINTR) => {}
+                Err(e) => {
+                    eprintln!("wait: {e}");
+                    break 1;
+                }
             }
+        };
+        if last || (pipefail && code != 0) {
+            status = if pipefail && code == 0 { status } else { code };
         }
     }
+    shell.jobs.forget(pgid);
+    if shell.interactive {
+        let _ = tty::give_terminal_back_to_shell();
+    }
+    Ok(status)
 }
@@ -0,0 +1,517 @@
+use crate::prelude::*;
+use crate::shell::util::quote::backslash_quote;
+use crate::shell::vars::is_valid_name;
+use crate::shell::Shell;
+
+/// Where backslash escapes are being read. They differ in octal escapes
+/// and in whether `\c` means "stop here".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscapeMode {
+    /// printf FORMAT: `\NNN`; `\c` is literal.
+    Format,
+    /// printf `%b` arguments: `\0NNN` or `\NNN`, `\c` stops.
+    Argument,
+    /// `echo -e`: only `\0NNN` is octal, `\c` stops.
+    Echo,
+}
+
+/// Expand backslash escapes in `s`. Returns the bytes (`\xHH` and octal
+/// escapes produce raw bytes) and whether a `\c` asked for all further
+/// output to be suppressed.
+pub fn expand_escapes(s: &str, mode: EscapeMode) -> (Vec<u8>, bool) {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '\\' {
+            push_char(&mut out, chars[i]);
+            i += 1;
+            continue;
+        }
+        let (bytes, len) = escape(&chars[i + 1..], mode);
+        match bytes {
+            Some(b) => out.extend_from_slice(&b),
+            None => return (out, true),
+        }
+        i += 1 + len;
+    }
+    (out, false)
+}
+
+/// The escape sequence after a backslash: what it stands for (None for a
+/// `\c` that stops output) and how many characters it used.
+fn escape(chars: &[char], mode: EscapeMode) -> (Option<Vec<u8>>, usize) {
+    let Some(&c) = chars.first() else { return (Some(b"\\".to_vec()), 0) };
+    let ch = match c {
+        'a' => '\x07',
+        'b' => '\x08',
+        'e' | 'E' => '\x1b',
+        'f' => '\x0c',
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'v' => '\x0b',
+        '\\' => '\\',
+        'c' if mode != EscapeMode::Format => return (None, 1),
+        '"' | '\'' | '?' if mode == EscapeMode::Format => c,
+        '0' if mode != EscapeMode::Format => {
+            let (n, len) = take_digits(&chars[1..], 8, 3);
+            return (Some(vec![n as u8]), 1 + len);
+        }
+        '0'..='7' if mode != EscapeMode::Echo => {
+            let (n, len) = take_digits(chars, 8, 3);
+            return (Some(vec![n as u8]), len);
+        }
+        'x' | 'u' | 'U' => {
+            let max = match c { 'x' => 2, 'u' => 4, _ => 8 };
+            let (n, len) = take_digits(&chars[1..], 16, max);
+            if len == 0 {
+                return (Some(format!("\\{c}").into_bytes()), 1);
+            }
+            if c == 'x' {
+                return (Some(vec![n as u8]), 1 + len);
+            }
+            return (Some(char::from_u32(n).map(|ch| ch.to_string().into_bytes()).unwrap_or_default()), 1 + len);
+        }
+        _ => return (Some(format!("\\{c}").into_bytes()), 1),
+    };
+    (Some(ch.to_string().into_bytes()), 1)
+}
+
+fn push_char(out: &mut Vec<u8>, c: char) {
+    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+/// Value of up to `max` leading digits in `radix`, and how many were used.
+fn take_digits(chars: &[char], radix: u32, max: usize) -> (u32, usize) {
+    let mut n = 0u32;
+    let mut len = 0;
+    while len < max {
+        match chars.get(len).and_then(|c| c.to_digit(radix)) {
+            Some(d) => n = n.saturating_mul(radix).saturating_add(d),
+            None => break,
+        }
+        len += 1;
+    }
+    (n, len)
+}
+
+/// printf [-v var] FORMAT [ARGS...]: print ARGS according to FORMAT,
+/// reusing FORMAT until every argument has been consumed.
+pub fn builtin_printf(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut args = args;
+    let mut var = None;
+    loop {
+        match args.first().map(String::as_str) {
+            Some("-v") if args.len() > 1 => {
+                var = Some(args[1].clone());
+                args = &args[2..];
+            }
+            Some("--") => {
+                args = &args[1..];
+                break;
+            }
+            _ => break,
+        }
+    }
+    let Some((format, operands)) = args.split_first() else {
+        eprintln!("printf: usage: printf [-v var] format [arguments]");
+        return Ok(2);
+    };
+    if let Some(name) = &var {
+        if !is_valid_name(name.split('[').next().unwrap_or_default()) {
+            eprintln!("printf: `{name}': not a valid identifier");
+            return Ok(2);
+        }
+    }
+
+    let mut f = Formatter { args: operands, next: 0, out: Vec::new(), status: 0 };
+    loop {
+        let before = f.next;
+        if !f.run(format) {
+            break;
+        }
+        // Reuse the format for leftover arguments, if it consumes any.
+        if f.next >= operands.len() || f.next == before {
+            break;
+        }
+    }
+
+    match var {
+        Some(name) => match name.split_once('[') {
+            Some((base, key)) => {
+                let value = String::from_utf8_lossy(&f.out).into_owned();
+                if let Err(e) = shell.vars.set_element(base, key.trim_end_matches(']'), value) {
+                    eprintln!("printf: {e}");
+                    return Ok(1);
+                }
+            }
+            None => shell.vars.set_scalar(&name, String::from_utf8_lossy(&f.out)),
+        },
+        None => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&f.out)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(f.status)
+}
+
+struct Formatter<'a> {
+    args: &'a [String],
+    /// Index of the next unused argument.
+    next: usize,
+    out: Vec<u8>,
+    status: i32,
+}
+
+/// `%[flags][width][.precision]conv`
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    zero: bool,
+    width: usize,
+    prec: Option<usize>,
+}
+
+impl Formatter<'_> {
+    /// One pass over the format. Returns false if output must stop
+    /// (`\c` in `%b`, or a bad conversion).
+    fn run(&mut self, format: &str) -> bool {
+        let chars: Vec<char> = format.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '\\' => {
+                    let (bytes, len) = escape(&chars[i + 1..], EscapeMode::Format);
+                    self.out.extend_from_slice(&bytes.unwrap_or_default());
+                    i += 1 + len;
+                }
+                '%' => {
+                    let Some(len) = self.conversion(&chars[i + 1..]) else { return false };
+                    i += 1 + len;
+                }
+                c => {
+                    push_char(&mut self.out, c);
+                    i += 1;
+                }
+            }
+        }
+        true
+    }
+
+    /// The next argument; missing ones read as empty.
+    fn next_arg(&mut self) -> String {
+        let arg = self.args.get(self.next).cloned().unwrap_or_default();
+        self.next += 1;
+        arg
+    }
+
+    /// Handle one conversion; `spec` starts after the `%`. Returns the
+    /// number of characters used, or None if output must stop (an error,
+    /// or `\c` in a `%b` argument).
+    fn conversion(&mut self, spec: &[char]) -> Option<usize> {
+        let mut s = Spec::default();
+        let mut i = 0;
+        while let Some(&c) = spec.get(i) {
+            match c {
+                '-' => s.left = true,
+                '+' => s.plus = true,
+                ' ' => s.space = true,
+                '#' => s.alt = true,
+                '0' => s.zero = true,
+                _ => break,
+            }
+            i += 1;
+        }
+        if spec.get(i) == Some(&'*') {
+            let a = self.next_arg();
+            let w = self.int_arg(&a);
+            s.left |= w < 0;
+            s.width = w.unsigned_abs() as usize;
+            i += 1;
+        } else {
+            let (w, len) = take_digits(&spec[i..], 10, usize::MAX);
+            s.width = w as usize;
+            i += len;
+        }
+        if spec.get(i) == Some(&'.') {
+            i += 1;
+            if spec.get(i) == Some(&'*') {
+                let a = self.next_arg();
+                let p = self.int_arg(&a);
+                s.prec = (p >= 0).then_some(p as usize);
+                i += 1;
+            } else {
+                let (p, len) = take_digits(&spec[i..], 10, usize::MAX);
+                s.prec = Some(p as usize);
+                i += len;
+            }
+        }
+        let Some(&conv) = spec.get(i) else {
+            eprintln!("printf: `%': missing format character");
+            self.status = 1;
+            return None;
+        };
+        let text: Vec<u8> = match conv {
+            '%' => {
+                self.out.push(b'%');
+                return Some(i + 1);
+            }
+            's' => {
+                let a = self.next_arg();
+                match s.prec {
+                    Some(p) => a.chars().take(p).collect::<String>().into_bytes(),
+                    None => a.into_bytes(),
+                }
+            }
+            'b' => {
+                let a = self.next_arg();
+                let (mut bytes, stop) = expand_escapes(&a, EscapeMode::Argument);
+                if let Some(p) = s.prec {
+                    bytes.truncate(p);
+                }
+                if stop {
+                    self.out.extend(pad(&s, bytes));
+                    return None;
+                }
+                bytes
+            }
+            'q' => {
+                let a = self.next_arg();
+                backslash_quote(&a).into_bytes()
+            }
+            'c' => {
+                let a = self.next_arg();
+                // An empty argument gives a NUL byte, as in C.
+                a.chars().next().unwrap_or('\0').to_string().into_bytes()
+            }
+            'd' | 'i' | 'u' | 'o' | 'x' | 'X' => {
+                let a = self.next_arg();
+                let n = self.int_arg(&a);
+                return Some(self.push_int(&s, conv, n, i));
+            }
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' => {
+                let a = self.next_arg();
+                let v = self.float_arg(&a);
+                self.out.extend(format_float(&s, conv, v).into_bytes());
+                return Some(i + 1);
+            }
+            c => {
+                eprintln!("printf: `{c}': invalid format character");
+                self.status = 1;
+                return None;
+            }
+        };
+        self.out.extend(pad(&s, text));
+        Some(i + 1)
+    }
+
+    fn push_int(&mut self, s: &Spec, conv: char, n: i64, used: usize) -> usize {
+        let (sign, mut digits) = match conv {
+            'd' | 'i' => {
+                let sign = if n < 0 { "-" } else if s.plus { "+" } else if s.space { " " } else { "" };
+                (sign, n.unsigned_abs().to_string())
+            }
+            // Negative values wrap around, as with C's unsigned conversions.
+            'u' => ("", (n as u64).to_string()),
+            'o' => ("", format!("{:o}", n as u64)),
+            'x' => ("", format!("{:x}", n as u64)),
+            _ => ("", format!("{:X}", n as u64)),
+        };
+        if let Some(p) = s.prec {
+            if p == 0 && n == 0 {
+                digits.clear();
+            }
+            while digits.len() < p {
+                digits.insert(0, '0');
+            }
+        }
+        let prefix = match conv {
+            'x' if s.alt && n != 0 => "0x",
+            'X' if s.alt && n != 0 => "0X",
+            'o' if s.alt && !digits.starts_with('0') => "0",
+            _ => "",
+        };
+        let zero_pad = s.zero && s.prec.is_none();
+        self.out.extend(pad_number(s, sign, prefix, digits, zero_pad).into_bytes());
+        used + 1
+    }
+
+    /// An integer argument: decimal, `0x` hex, `0` octal or `'c` (the
+    /// character's code). Bad input is reported and what parsed is used.
+    fn int_arg(&mut self, arg: &str) -> i64 {
+        let t = arg.trim_start();
+        if t.is_empty() {
+            return 0;
+        }
+        if let Some(rest) = t.strip_prefix(['\'', '"']) {
+            return rest.chars().next().map_or(0, |c| c as i64);
+        }
+        let (neg, body) = match t.strip_prefix('-') {
+            Some(b) => (true, b),
+            None => (false, t.strip_prefix('+').unwrap_or(t)),
+        };
+        let (radix, digits) = if let Some(h) = body.strip_prefix("0x").or_else(|| body.strip_prefix("0X")) {
+            (16, h)
+        } else if body.len() > 1 && body.starts_with('0') {
+            (8, &body[1..])
+        } else {
+            (10, body)
+        };
+        let valid = digits.chars().take_while(|c| c.is_digit(radix)).count();
+        let mut n: i128 = 0;
+        for c in digits[..valid].chars() {
+            n = (n * radix as i128 + c.to_digit(radix).unwrap_or(0) as i128).min(i128::from(u64::MAX) + 1);
+        }
+        if neg {
+            n = -n;
+        }
+        if valid < digits.len() || (valid == 0 && radix != 8) {
+            eprintln!("printf: {arg}: invalid number");
+            self.status = 1;
+        } else if n > i64::MAX as i128 || n < i64::MIN as i128 {
+            eprintln!("printf: warning: {arg}: Numerical result out of range");
+        }
+        n.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+    }
+
+    fn float_arg(&mut self, arg: &str) -> f64 {
+        let t = arg.trim();
+        if t.is_empty() {
+            return 0.0;
+        }
+        if let Some(rest) = t.strip_prefix(['\'', '"']) {
+            return rest.chars().next().map_or(0.0, |c| c as u32 as f64);
+        }
+        if let Ok(v) = t.parse::<f64>() {
+            return v;
+        }
+        // Use the longest prefix that is a number, like strtod.
+        eprintln!("printf: {arg}: invalid number");
+        self.status = 1;
+        (1..t.len()).rev()
+            .filter(|&n| t.is_char_boundary(n))
+            .find_map(|n| t[..n].parse::<f64>().ok())
+            .unwrap_or(0.0)
+    }
+}
+
+/// Pad `text` to the spec's width (in characters, if it is UTF-8) with spaces.
+fn pad(s: &Spec, text: Vec<u8>) -> Vec<u8> {
+    let len = std::str::from_utf8(&text).map_or(text.len(), |t| t.chars().count());
+    if len >= s.width {
+        return text;
+    }
+    let fill = vec![b' '; s.width - len];
+    if s.left { [text, fill].concat() } else { [fill, text].concat() }
+}
+
+/// Assemble sign, prefix and digits, padding with zeros between the prefix
+/// and the digits (`%05d` → `-0042`) or with spaces outside.
+fn pad_number(s: &Spec, sign: &str, prefix: &str, digits: String, zero_pad: bool) -> String {
+    let len = sign.len() + prefix.len() + digits.len();
+    if zero_pad && !s.left && len < s.width {
+        return format!("{sign}{prefix}{}{digits}", "0".repeat(s.width - len));
+    }
+    String::from_utf8(pad(s, format!("{sign}{prefix}{digits}").into_bytes())).unwrap_or_default()
+}
+
+/// `%e`, `%f` and `%g` with C semantics.
+fn format_float(s: &Spec, conv: char, v: f64) -> String {
+    let upper = conv.is_ascii_uppercase();
+    let sign = if v.is_sign_negative() && !v.is_nan() { "-" } else if s.plus { "+" } else if s.space { " " } else { "" };
+    let a = v.abs();
+    if !a.is_finite() {
+        let text = if a.is_nan() { "nan" } else { "inf" };
+        let text = if upper { text.to_uppercase() } else { text.to_string() };
+        return pad_number(s, sign, "", text, false);
+    }
+    let prec = s.prec.unwrap_or(6);
+    let mut body = match conv.to_ascii_lowercase() {
+        'f' => format!("{a:.prec$}"),
+        'e' => exp_notation(a, prec),
+        _ => {
+            // %g: %e if the exponent is < -4 or >= the precision, else %f;
+            // then trailing zeros go (unless `#`).
+            let p = prec.max(1);
+            let exp = exp_notation(a, p - 1);
+            let x: i32 = exp.rsplit('e').next().and_then(|e| e.parse().ok()).unwrap_or(0);
+            let mut out = if x < -4 || x >= p as i32 { exp } else { format!("{a:.*}", (p as i32 - 1 - x) as usize) };
+            if !s.alt {
+                out = strip_trailing_zeros(&out);
+            }
+            out
+        }
+    };
+    if s.alt && !body.contains('.') {
+        match body.find('e') {
+            Some(e) => body.insert(e, '.'),
+            None => body.push('.'),
+        }
+    }
+    if upper {
+        body = body.to_uppercase();
+    }
+    pad_number(s, sign, "", body, s.zero)
+}
+
+/// `d.ddde+XX` with `prec` fraction digits and at least two exponent digits.
+fn exp_notation(a: f64, prec: usize) -> String {
+    let raw = format!("{a:.prec$e}");
+    let (mantissa, exp) = raw.split_once('e').unwrap_or((&raw, "0"));
+    let exp: i32 = exp.parse().unwrap_or(0);
+    format!("{mantissa}e{}{:02}", if exp < 0 { '-' } else { '+' }, exp.abs())
+}
+
+fn strip_trailing_zeros(s: &str) -> String {
+    let (mantissa, exp) = match s.find('e') {
+        Some(i) => s.split_at(i),
+        None => (s, ""),
+    };
+    let mantissa = if mantissa.contains('.') { mantissa.trim_end_matches('0').trim_end_matches('.') } else { mantissa };
+    format!("{mantissa}{exp}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::vars::VarStore;
+
+    /// `printf -v out format args...`, then `$out`.
+    fn printf(format: &str, args: &[&str]) -> String {
+        let mut shell = Shell::with_vars(VarStore::default()).unwrap();
+        let mut argv = vec!["-v".to_string(), "out".to_string(), format.to_string()];
+        argv.extend(args.iter().map(|a| a.to_string()));
+        builtin_printf(&mut shell, &argv).unwrap();
+        shell.vars.get_str("out").unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn integer_and_float_conversions() {
+        assert_eq!(printf("[%5.2f|%-4s|%04d|%+d]", &["3.14159", "ab", "42", "7"]), "[ 3.14|ab  |0042|+7]");
+        assert_eq!(printf("[%x|%X|%#o|%#x]", &["255", "255", "8", "255"]), "[ff|FF|010|0xff]");
+        assert_eq!(printf("[%e|%g|%G]", &["1234.5", "0.0001", "1e-10"]), "[1.234500e+03|0.0001|1E-10]");
+        assert_eq!(printf("[%.3e|%g|%g]", &["0.5", "100000", "1000000"]), "[5.000e-01|100000|1e+06]");
+        // A leading quote gives the character's code.
+        assert_eq!(printf("[%d]", &["'A"]), "[65]");
+    }
+
+    #[test]
+    fn string_conversions_and_escapes() {
+        assert_eq!(printf("[%c|%s|%10.3s|]", &["hello", "w", "abcdef"]), "[h|w|       abc|]");
+        assert_eq!(printf("%5s%%\\n", &["x"]), "    x%\n");
+        // `\c` in a %b argument ends all output.
+        assert_eq!(printf("[%b]", &["a\\tb\\c ignored"]), "[a\tb");
+    }
+
+    #[test]
+    fn the_format_is_reused_for_leftover_arguments() {
+        assert_eq!(printf("[%d %d]", &["1", "2", "3"]), "[1 2][3 0]");
+        assert_eq!(printf("%s-", &["a", "b", "c"]), "a-b-c-");
+        assert_eq!(printf("plain", &["unused"]), "plain");
+    }
+}
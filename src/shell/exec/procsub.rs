@@ -0,0 +1,112 @@
+//! Process substitution: a `<(list)` or `>(list)` word runs `list` in a
+//! child and stands for a `/dev/fd/N` path the command reads its output
+//! from or writes its input to.
+
+use crate::prelude::*;
+use crate::shell::parser::ast::{Redirect, SimpleCommand};
+use crate::shell::scripting::run_script_string;
+use crate::shell::Shell;
+
+#[cfg(unix)]
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, IntoRawFd};
+
+/// The list inside a `<(list)` or `>(list)` word, and whether the command
+/// reads from it (`<`).
+fn split(word: &str) -> Option<(bool, &str)> {
+    let reads = word.starts_with('<');
+    let list = word.strip_prefix("<(").or_else(|| word.strip_prefix(">("))?.strip_suffix(')')?;
+    Some((reads, list))
+}
+
+/// Does `p` have a process substitution among its words or redirections?
+pub fn any_in(words: &[String], redirects: &[Redirect]) -> bool {
+    words.iter().chain(redirects.iter().map(|r| &r.target)).any(|w| split(w).is_some())
+}
+
+/// `p` with each process substitution started and replaced by its path.
+/// The shell's ends stay open until `close_from` is called.
+pub fn substitute_simple(shell: &mut Shell, p: &SimpleCommand) -> Result<SimpleCommand> {
+    let mut p = p.clone();
+    for w in &mut p.words {
+        substitute(shell, w)?;
+    }
+    p.redirects = substitute_redirects(shell, &p.redirects)?;
+    Ok(p)
+}
+
+/// `redirects` with process substitutions in their targets started.
+pub fn substitute_redirects(shell: &mut Shell, redirects: &[Redirect]) -> Result<Vec<Redirect>> {
+    let mut redirects = redirects.to_vec();
+    for r in &mut redirects {
+        substitute(shell, &mut r.target)?;
+    }
+    Ok(redirects)
+}
+
+/// Start the list in `word`, if it is a process substitution, and put the
+/// path of the shell's end of its pipe in its place.
+#[cfg(unix)]
+fn substitute(shell: &mut Shell, word: &mut String) -> Result<()> {
+    let Some((reads, list)) = split(word) else { return Ok(()) };
+    let list = list.to_string();
+    let (r, w) = super::cloexec_pipe()?;
+    let (theirs, kept) = if reads { (w, r) } else { (r, w) };
+    let stdio = if reads { (None, Some(theirs.as_raw_fd())) } else { (Some(theirs.as_raw_fd()), None) };
+    let mut close_fds = shell.proc_sub_fds.clone();
+    close_fds.extend([theirs.as_raw_fd(), kept.as_raw_fd()]);
+    super::fork_child(shell, None, false, stdio, &close_fds, |sh| run_script_string(sh, "process substitution", &list))?;
+    // The command opens `/dev/fd/N`, so programs it execs must inherit N.
+    fcntl(kept.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::empty()))?;
+    let fd = kept.into_raw_fd();
+    shell.proc_sub_fds.push(fd);
+    *word = format!("/dev/fd/{fd}");
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn substitute(_shell: &mut Shell, word: &mut String) -> Result<()> {
+    match split(word) {
+        Some(_) => Err("process substitution is not supported on this platform".into()),
+        None => Ok(()),
+    }
+}
+
+/// Close the shell's ends of the substitutions started since `mark`
+/// (a length of `shell.proc_sub_fds`), once their command has run.
+pub fn close_from(shell: &mut Shell, mark: usize) {
+    for fd in shell.proc_sub_fds.drain(mark..) {
+        #[cfg(unix)]
+        let _ = nix::unistd::close(fd);
+        #[cfg(not(unix))]
+        let _ = fd;
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::shell::scripting::run_script_string;
+    use crate::shell::vars::VarStore;
+    use crate::shell::Shell;
+
+    fn run(src: &str) -> Shell {
+        let mut shell = Shell::with_vars(VarStore::default()).unwrap();
+        run_script_string(&mut shell, "test", src).unwrap();
+        shell
+    }
+
+    #[test]
+    fn reads_the_output_of_a_list() {
+        let sh = run("read a b < <(echo one; (echo nested))");
+        assert_eq!(sh.vars.get_str("a"), Some("one"));
+        assert!(sh.proc_sub_fds.is_empty());
+    }
+
+    #[test]
+    fn the_word_is_a_dev_fd_path() {
+        let sh = run("set -- <(true) '<(true)'");
+        assert!(sh.positional[0].starts_with("/dev/fd/"));
+        assert_eq!(sh.positional[1], "<(true)");
+    }
+}
@@ -0,0 +1,460 @@
+use crate::prelude::*;
+use crate::shell::vars::{is_valid_name, Value};
+use crate::shell::Shell;
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use nix::libc;
+
+/// One byte of input, or why there is none.
+pub enum Byte {
+    Data(u8),
+    Eof,
+    TimedOut,
+}
+
+/// Unbuffered input from a file descriptor. Bytes are read one at a time,
+/// so whatever follows a line stays in the fd for the next command.
+pub struct FdInput {
+    fd: i32,
+    deadline: Option<Instant>,
+}
+
+impl FdInput {
+    pub fn new(fd: i32) -> Self {
+        Self { fd, deadline: None }
+    }
+
+    /// Give up with `Byte::TimedOut` once `timeout` has passed.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.deadline = timeout.map(|t| Instant::now() + t);
+        self
+    }
+
+    #[cfg(unix)]
+    pub fn next_byte(&mut self) -> io::Result<Byte> {
+        loop {
+            if let Some(deadline) = self.deadline {
+                // Checked before every byte, so input that keeps coming
+                // (`/dev/zero`) can't outlast the timeout either.
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    return Ok(Byte::TimedOut);
+                }
+                let ms = left.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32;
+                let mut pfd = libc::pollfd { fd: self.fd, events: libc::POLLIN, revents: 0 };
+                match unsafe { libc::poll(&mut pfd, 1, ms) } {
+                    0 => return Ok(Byte::TimedOut),
+                    n if n < 0 => {
+                        let e = io::Error::last_os_error();
+                        if e.kind() == io::ErrorKind::Interrupted {
+                            continue;
+                        }
+                        return Err(e);
+                    }
+                    _ => {}
+                }
+            }
+            let mut b = 0u8;
+            match unsafe { libc::read(self.fd, (&mut b as *mut u8).cast(), 1) } {
+                1 => return Ok(Byte::Data(b)),
+                0 => return Ok(Byte::Eof),
+                _ => {
+                    let e = io::Error::last_os_error();
+                    if e.kind() != io::ErrorKind::Interrupted {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn next_byte(&mut self) -> io::Result<Byte> {
+        use std::io::Read;
+        let mut b = [0u8];
+        Ok(match io::stdin().read(&mut b)? {
+            0 => Byte::Eof,
+            _ => Byte::Data(b[0]),
+        })
+    }
+
+    /// Bytes up to `delim`, and whether `delim` was seen (it isn't included).
+    /// None at end of input when nothing was read.
+    pub fn read_until(&mut self, delim: u8) -> io::Result<Option<(Vec<u8>, bool)>> {
+        let mut out = Vec::new();
+        loop {
+            match self.next_byte()? {
+                Byte::Data(b) if b == delim => return Ok(Some((out, true))),
+                Byte::Data(b) => out.push(b),
+                Byte::Eof | Byte::TimedOut if out.is_empty() => return Ok(None),
+                Byte::Eof | Byte::TimedOut => return Ok(Some((out, false))),
+            }
+        }
+    }
+}
+
+/// Is `fd` open? Reports it as NAME's error if not.
+#[cfg(unix)]
+pub fn check_fd(name: &str, fd: i32) -> bool {
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        eprintln!("{name}: {fd}: invalid file descriptor: Bad file descriptor");
+        return false;
+    }
+    true
+}
+
+#[cfg(not(unix))]
+pub fn check_fd(_name: &str, _fd: i32) -> bool {
+    true
+}
+
+/// Can `fd` be read without blocking?
+#[cfg(unix)]
+fn input_waiting(fd: i32) -> bool {
+    let mut pfd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+    unsafe { libc::poll(&mut pfd, 1, 0) > 0 }
+}
+
+#[cfg(not(unix))]
+fn input_waiting(_fd: i32) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn is_tty(fd: i32) -> bool {
+    unsafe { libc::isatty(fd) == 1 }
+}
+
+#[cfg(not(unix))]
+fn is_tty(_fd: i32) -> bool {
+    false
+}
+
+/// Puts the terminal back as it was when dropped.
+#[cfg(unix)]
+struct TermGuard(i32, nix::sys::termios::Termios);
+
+#[cfg(unix)]
+impl TermGuard {
+    /// Turn off echo (`-s`) and/or line buffering (`-n`, `-N`) on `fd`.
+    fn set(fd: i32, silent: bool, by_char: bool) -> Option<TermGuard> {
+        use nix::sys::termios::{tcgetattr, tcsetattr, LocalFlags, SetArg, SpecialCharacterIndices};
+        let borrowed = unsafe { std::os::fd::BorrowedFd::borrow_raw(fd) };
+        let orig = tcgetattr(borrowed).ok()?;
+        let mut t = orig.clone();
+        if silent {
+            t.local_flags.remove(LocalFlags::ECHO);
+        }
+        if by_char {
+            t.local_flags.remove(LocalFlags::ICANON);
+            t.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+            t.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+        }
+        tcsetattr(borrowed, SetArg::TCSADRAIN, &t).ok()?;
+        Some(TermGuard(fd, orig))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for TermGuard {
+    fn drop(&mut self) {
+        use nix::sys::termios::{tcsetattr, SetArg};
+        let fd = unsafe { std::os::fd::BorrowedFd::borrow_raw(self.0) };
+        let _ = tcsetattr(fd, SetArg::TCSADRAIN, &self.1);
+    }
+}
+
+#[derive(Default)]
+struct ReadOptions {
+    raw: bool,
+    silent: bool,
+    prompt: Option<String>,
+    timeout: Option<Duration>,
+    /// `-n N` (stop early at the delimiter) or `-N N` (exactly N, no splitting).
+    nchars: Option<(usize, bool)>,
+    delim: u8,
+    array: Option<String>,
+    fd: i32,
+}
+
+const USAGE: &str =
+    "read: usage: read [-rs] [-a array] [-d delim] [-n nchars] [-N nchars] [-p prompt] [-t timeout] [-u fd] [name ...]";
+
+/// Parse read's options; Err is the status to return.
+fn parse_options(args: &[String]) -> std::result::Result<(ReadOptions, usize), i32> {
+    let mut o = ReadOptions { delim: b'\n', ..Default::default() };
+    let mut i = 0;
+    while let Some(a) = args.get(i).filter(|a| a.starts_with('-') && a.len() > 1) {
+        i += 1;
+        if a == "--" {
+            break;
+        }
+        for (pos, c) in a.char_indices().skip(1) {
+            if matches!(c, 'r' | 's') {
+                if c == 'r' { o.raw = true } else { o.silent = true }
+                continue;
+            }
+            if !"pntNdau".contains(c) {
+                eprintln!("read: -{c}: invalid option");
+                eprintln!("{USAGE}");
+                return Err(2);
+            }
+            // The value is the rest of this word, or the next argument.
+            let value = match &a[pos + c.len_utf8()..] {
+                "" => match args.get(i) {
+                    Some(v) => {
+                        i += 1;
+                        v.clone()
+                    }
+                    None => {
+                        eprintln!("read: -{c}: option requires an argument");
+                        eprintln!("{USAGE}");
+                        return Err(2);
+                    }
+                },
+                rest => rest.to_string(),
+            };
+            match c {
+                'p' => o.prompt = Some(value),
+                'a' => o.array = Some(value),
+                'd' => o.delim = value.bytes().next().unwrap_or(0),
+                't' => match value.parse::<f64>() {
+                    Ok(t) if t >= 0.0 && t.is_finite() => o.timeout = Some(Duration::from_secs_f64(t)),
+                    _ => {
+                        eprintln!("read: {value}: invalid timeout specification");
+                        return Err(1);
+                    }
+                },
+                'u' => match value.parse::<i32>() {
+                    Ok(fd) if fd >= 0 => o.fd = fd,
+                    _ => {
+                        eprintln!("read: {value}: invalid file descriptor specification");
+                        return Err(1);
+                    }
+                },
+                _ => match value.parse::<usize>() {
+                    Ok(n) => o.nchars = Some((n, c == 'N')),
+                    Err(_) => {
+                        eprintln!("read: {value}: invalid number");
+                        return Err(1);
+                    }
+                },
+            }
+            break;
+        }
+    }
+    Ok((o, i))
+}
+
+/// read [-rs] [-a array] [-d delim] [-n|-N nchars] [-p prompt] [-t timeout]
+/// [-u fd] [name ...]: read a line and split it on IFS into the names, the
+/// last one getting the rest of the line. Without names the line goes to
+/// REPLY. Status 1 at end of input, 128+SIGALRM after a timeout.
+pub fn builtin_read(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let (o, first) = match parse_options(args) {
+        Ok(v) => v,
+        Err(status) => return Ok(status),
+    };
+    let names = &args[first..];
+    if let Some(bad) = names.iter().chain(&o.array).find(|n| !is_valid_name(n)) {
+        eprintln!("read: `{bad}': not a valid identifier");
+        return Ok(1);
+    }
+    if !check_fd("read", o.fd) {
+        return Ok(1);
+    }
+
+    // `-t 0`: only report whether input is waiting.
+    if o.timeout == Some(Duration::ZERO) {
+        return Ok(if input_waiting(o.fd) { 0 } else { 1 });
+    }
+
+    let tty = is_tty(o.fd);
+    if let (Some(prompt), true) = (&o.prompt, tty) {
+        eprint!("{prompt}");
+        let _ = io::stderr().flush();
+    }
+    #[cfg(unix)]
+    let guard = if tty && (o.silent || o.nchars.is_some()) { TermGuard::set(o.fd, o.silent, o.nchars.is_some()) } else { None };
+    let (line, status) = read_chars(&o)?;
+    #[cfg(unix)]
+    drop(guard);
+
+    let ifs = super::expand::ifs(shell);
+    let exact = matches!(o.nchars, Some((_, true)));
+    if let Some(array) = &o.array {
+        let fields = if exact { vec![to_string(&line)] } else { split_fields(&line, ifs.as_bytes(), None) };
+        shell.vars.unset(array);
+        shell.vars.set_value(array, Value::Indexed(fields.into_iter().enumerate().collect()));
+    } else if names.is_empty() {
+        shell.vars.set_scalar("REPLY", to_string(&line));
+    } else if exact {
+        shell.vars.set_scalar(&names[0], to_string(&line));
+        for name in &names[1..] {
+            shell.vars.set_scalar(name, "");
+        }
+    } else {
+        let mut fields = split_fields(&line, ifs.as_bytes(), Some(names.len())).into_iter();
+        for name in names {
+            shell.vars.set_scalar(name, fields.next().unwrap_or_default());
+        }
+    }
+    Ok(status)
+}
+
+/// Read up to the delimiter (or the character count). Each byte comes with
+/// whether a backslash quoted it, which keeps it from splitting fields.
+fn read_chars(o: &ReadOptions) -> Result<(Vec<(u8, bool)>, i32)> {
+    let mut input = FdInput::new(o.fd).with_timeout(o.timeout);
+    let mut line = Vec::new();
+    let (limit, exact) = match o.nchars {
+        Some((n, exact)) => (Some(n), exact),
+        None => (None, false),
+    };
+    let mut chars = 0;
+    // Continuation bytes still to come for the current UTF-8 character.
+    let mut pending = 0;
+    let mut quoted = false;
+    loop {
+        if pending == 0 && limit.is_some_and(|n| chars >= n) {
+            return Ok((line, 0));
+        }
+        let b = match input.next_byte()? {
+            Byte::Data(b) => b,
+            Byte::Eof => return Ok((line, 1)),
+            Byte::TimedOut => return Ok((line, 128 + 14)),
+        };
+        let escaped = std::mem::take(&mut quoted);
+        if escaped {
+            // Backslash-newline continues the line.
+            if b == b'\n' {
+                continue;
+            }
+        } else if b == o.delim && !exact {
+            return Ok((line, 0));
+        } else if b == b'\\' && !o.raw {
+            quoted = true;
+            continue;
+        }
+        line.push((b, escaped));
+        if pending > 0 {
+            pending -= 1;
+        } else {
+            chars += 1;
+            pending = match b {
+                0xc0..=0xdf => 1,
+                0xe0..=0xef => 2,
+                0xf0..=0xf7 => 3,
+                _ => 0,
+            };
+        }
+    }
+}
+
+fn to_string(line: &[(u8, bool)]) -> String {
+    String::from_utf8_lossy(&line.iter().map(|&(b, _)| b).collect::<Vec<u8>>()).into_owned()
+}
+
+/// Split `line` on `ifs` into at most `count` fields, the last holding the
+/// rest of the line: IFS whitespace around fields is dropped, and other IFS
+/// characters each end one field. Quoted bytes never split.
+fn split_fields(line: &[(u8, bool)], ifs: &[u8], count: Option<usize>) -> Vec<String> {
+    let is_sep = |&(b, quoted): &(u8, bool)| !quoted && ifs.contains(&b);
+    let is_ws = |c: &(u8, bool)| is_sep(c) && matches!(c.0, b' ' | b'\t' | b'\n');
+    let trim_end = |s: &[(u8, bool)]| s.len() - s.iter().rev().take_while(|c| is_ws(c)).count();
+
+    let mut fields = Vec::new();
+    let mut i = line.iter().take_while(|c| is_ws(c)).count();
+    while i < line.len() {
+        if count == Some(fields.len() + 1) {
+            let mut rest = &line[i..];
+            rest = &rest[..trim_end(rest)];
+            // One field followed by a lone delimiter: the delimiter goes.
+            if let Some((last, body)) = rest.split_last() {
+                if is_sep(last) && !body.iter().any(is_sep) {
+                    rest = &body[..trim_end(body)];
+                }
+            }
+            fields.push(to_string(rest));
+            break;
+        }
+        let start = i;
+        while i < line.len() && !is_sep(&line[i]) {
+            i += 1;
+        }
+        fields.push(to_string(&line[start..i]));
+        while i < line.len() && is_ws(&line[i]) {
+            i += 1;
+        }
+        if i < line.len() && is_sep(&line[i]) {
+            i += 1;
+            while i < line.len() && is_ws(&line[i]) {
+                i += 1;
+            }
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `s` as read: a backslash quotes the byte after it.
+    fn line(s: &str) -> Vec<(u8, bool)> {
+        let mut out = Vec::new();
+        let mut bytes = s.bytes();
+        while let Some(b) = bytes.next() {
+            match b {
+                b'\\' => out.extend(bytes.next().map(|b| (b, true))),
+                b => out.push((b, false)),
+            }
+        }
+        out
+    }
+
+    fn split(s: &str, ifs: &str, count: Option<usize>) -> Vec<String> {
+        split_fields(&line(s), ifs.as_bytes(), count)
+    }
+
+    #[test]
+    fn whitespace_ifs_drops_blanks_around_fields() {
+        assert_eq!(split("  a  b\tc  ", " \t\n", None), ["a", "b", "c"]);
+        assert_eq!(split("   ", " \t\n", None), Vec::<String>::new());
+        assert_eq!(split("a b", "", None), ["a b"]);
+    }
+
+    #[test]
+    fn other_ifs_characters_each_end_a_field() {
+        assert_eq!(split("a:b::c", ":", None), ["a", "b", "", "c"]);
+        assert_eq!(split(" a : b ", " :", None), ["a", "b"]);
+    }
+
+    #[test]
+    fn the_last_field_takes_the_rest_of_the_line() {
+        assert_eq!(split("a b  c d  ", " ", Some(2)), ["a", "b  c d"]);
+        // A lone trailing delimiter is dropped, a second one is kept.
+        assert_eq!(split("a b:", " :", Some(2)), ["a", "b"]);
+        assert_eq!(split("a b:c:", " :", Some(2)), ["a", "b:c:"]);
+    }
+
+    #[test]
+    fn quoted_bytes_never_split() {
+        assert_eq!(split("a\\ b c", " ", None), ["a b", "c"]);
+        assert_eq!(split("a\\:b:c", ":", Some(1)), ["a:b:c"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn timeout_ends_input_that_never_stops() {
+        use std::os::fd::AsRawFd;
+        let zero = std::fs::File::open("/dev/zero").unwrap();
+        let started = Instant::now();
+        let mut input = FdInput::new(zero.as_raw_fd()).with_timeout(Some(Duration::from_millis(50)));
+        let (_, found) = input.read_until(b'\n').unwrap().unwrap();
+        assert!(!found);
+        assert!(matches!(input.next_byte().unwrap(), Byte::TimedOut));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,162 @@
+use super::expand::expand_word_single;
+use crate::prelude::*;
+use crate::shell::parser::ast::Redirect;
+use crate::shell::parser::lexer::RedirOp;
+use crate::shell::Shell;
+
+#[cfg(unix)]
+use nix::{
+    fcntl::{fcntl, open, FcntlArg, FdFlag, OFlag},
+    sys::stat::Mode,
+    unistd::{close, dup2},
+};
+#[cfg(unix)]
+use std::{os::fd::RawFd, path::Path};
+
+/// Descriptors replaced by `apply`, with the saved copy to put back
+/// (None if the fd was closed before).
+#[cfg(unix)]
+#[derive(Default)]
+pub struct Saved(Vec<(RawFd, Option<RawFd>)>);
+
+/// Lowest fd used for the shell's saved copies, out of the way of `3>file`.
+#[cfg(unix)]
+const SAVE_FD_BASE: RawFd = 10;
+
+/// Perform `redirects` on this process. With `save`, the previous targets
+/// are kept so `restore` can undo them (builtins, functions, compound
+/// commands); forked children pass `save = false`.
+#[cfg(unix)]
+pub fn apply(shell: &Shell, redirects: &[Redirect], save: bool) -> std::result::Result<Saved, String> {
+    let mut saved = Saved::default();
+    for r in redirects {
+        if let Err(e) = apply_one(shell, r, save, &mut saved) {
+            restore(saved);
+            return Err(e);
+        }
+    }
+    Ok(saved)
+}
+
+#[cfg(unix)]
+fn apply_one(shell: &Shell, r: &Redirect, save: bool, saved: &mut Saved) -> std::result::Result<(), String> {
+    let target = expand_word_single(shell, &r.target)?;
+    let fd = r.fd.unwrap_or(match r.op {
+        RedirOp::In | RedirOp::DupIn | RedirOp::ReadWrite => 0,
+        _ => 1,
+    });
+    let create = Mode::from_bits_truncate(0o666);
+    // Opened CLOEXEC so a failed dup2 can't leak the fd into children.
+    let cloexec = OFlag::O_CLOEXEC;
+    // Save fd before opening anything, which could land on it if it's free.
+    prepare(fd, save, saved);
+    let src = match r.op {
+        RedirOp::DupIn | RedirOp::DupOut if target == "-" => {
+            // `n<&-` / `n>&-`: close n.
+            let _ = close(fd);
+            return Ok(());
+        }
+        RedirOp::DupIn | RedirOp::DupOut => {
+            let n: RawFd = target.parse().map_err(|_| format!("{target}: ambiguous redirect"))?;
+            if fcntl(n, FcntlArg::F_GETFD).is_err() {
+                return Err(format!("{n}: Bad file descriptor"));
+            }
+            if n == fd {
+                return Ok(());
+            }
+            dup2(n, fd).map_err(|e| format!("{n}: {}", e.desc()))?;
+            return Ok(());
+        }
+        RedirOp::In => open(target.as_str(), OFlag::O_RDONLY | cloexec, Mode::empty()),
+        RedirOp::Out if shell.options.get("noclobber") && Path::new(&target).is_file() => {
+            return Err(format!("{target}: cannot overwrite existing file"));
+        }
+        RedirOp::Out | RedirOp::Clobber => {
+            open(target.as_str(), OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_TRUNC | cloexec, create)
+        }
+        RedirOp::Append => open(target.as_str(), OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_APPEND | cloexec, create),
+        RedirOp::ReadWrite => open(target.as_str(), OFlag::O_RDWR | OFlag::O_CREAT | cloexec, create),
+    }
+    .map_err(|e| format!("{target}: {}", e.desc()))?;
+
+    if src == fd {
+        // The open landed on the requested fd (e.g. `exec 3>file` with 3 free).
+        let _ = fcntl(fd, FcntlArg::F_SETFD(FdFlag::empty()));
+    } else {
+        let res = dup2(src, fd);
+        let _ = close(src);
+        res.map_err(|e| format!("{target}: {}", e.desc()))?;
+    }
+    Ok(())
+}
+
+/// Flush buffered output headed for `fd` and remember what it pointed at.
+#[cfg(unix)]
+fn prepare(fd: RawFd, save: bool, saved: &mut Saved) {
+    flush_std(fd);
+    // `10>file` may hit one of our own saved copies: move it out of the way.
+    for (_, copy) in saved.0.iter_mut() {
+        if *copy == Some(fd) {
+            *copy = fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(SAVE_FD_BASE)).ok();
+            let _ = close(fd);
+        }
+    }
+    if save && !saved.0.iter().any(|(f, _)| *f == fd) {
+        let copy = fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(SAVE_FD_BASE)).ok();
+        saved.0.push((fd, copy));
+    }
+}
+
+#[cfg(unix)]
+fn flush_std(fd: RawFd) {
+    match fd {
+        1 => { let _ = io::stdout().flush(); }
+        2 => { let _ = io::stderr().flush(); }
+        _ => {}
+    }
+}
+
+/// Undo `apply`, newest first.
+#[cfg(unix)]
+pub fn restore(saved: Saved) {
+    for (fd, copy) in saved.0.into_iter().rev() {
+        flush_std(fd);
+        match copy {
+            Some(copy) => {
+                let _ = dup2(copy, fd);
+                let _ = close(copy);
+            }
+            None => { let _ = close(fd); }
+        }
+    }
+}
+
+/// Run `f` with `redirects` applied to the shell itself, then put the
+/// descriptors back. A failed redirection skips `f` with status 1.
+pub fn with_redirects(
+    shell: &mut Shell,
+    redirects: &[Redirect],
+    f: impl FnOnce(&mut Shell) -> Result<i32>,
+) -> Result<i32> {
+    if redirects.is_empty() {
+        return f(shell);
+    }
+    #[cfg(unix)]
+    {
+        let saved = match apply(shell, redirects, true) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("0-shell: {e}");
+                return Ok(1);
+            }
+        };
+        let status = f(shell);
+        restore(saved);
+        status
+    }
+    #[cfg(not(unix))]
+    {
+        eprintln!("0-shell: redirections are not supported on this platform");
+        Ok(1)
+    }
+}
@@ -1,8 +1,57 @@
-/// 0 = success, non-zero = error.
-pub const EXIT_SIGNAL: i32 = -7777;
+use crate::prelude::AnyError;
+use std::fmt;
 
+/// 0 = success, non-zero = error.
 #[inline]
 pub fn ok() -> i32 { 0 }
 
 #[inline]
 pub fn err() -> i32 { 1 }
+
+/// Control flow that unwinds past the commands being run. It travels in
+/// the error half of `Result`, so `?` carries it up to the loop, function
+/// or shell that handles it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// `exit N`, and failures that end the shell (`set -e`, `set -u`).
+    Exit(i32),
+    /// `return N` from a function or sourced file.
+    Return(i32),
+    /// `break N`: leave N enclosing loops.
+    Break(usize),
+    /// `continue N`: next iteration of the Nth enclosing loop.
+    Continue(usize),
+}
+
+impl Flow {
+    /// The flow carried by `err`, if it is one.
+    pub fn of(err: &AnyError) -> Option<Flow> {
+        err.downcast_ref::<Flow>().copied()
+    }
+}
+
+impl fmt::Display for Flow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Flow::Exit(n) => write!(f, "exit {n}"),
+            Flow::Return(n) => write!(f, "return {n}"),
+            Flow::Break(n) => write!(f, "break {n}"),
+            Flow::Continue(n) => write!(f, "continue {n}"),
+        }
+    }
+}
+
+impl std::error::Error for Flow {}
+
+/// Status for the end of a shell: `exit N` gives N, other flows that got
+/// this far leave `last`, and real errors are passed on.
+pub fn exit_status(result: crate::prelude::Result<i32>, last: i32) -> crate::prelude::Result<i32> {
+    match result {
+        Ok(status) => Ok(status),
+        Err(e) => match Flow::of(&e) {
+            Some(Flow::Exit(n)) => Ok(n),
+            Some(_) => Ok(last),
+            None => Err(e),
+        },
+    }
+}
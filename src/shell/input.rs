@@ -1,5 +1,5 @@
 use crate::prelude::*;
-use std::io::{Read, Write};
+use std::io::{BufRead, IsTerminal, Read, Write};
 
 #[cfg(unix)]
 use nix::sys::termios::{tcgetattr, tcsetattr, LocalFlags, SetArg, Termios};
@@ -8,21 +8,29 @@ use std::os::fd::AsRawFd;
 
 /// Read one line with in-memory history and Up/Down navigation (Unix).
 /// Returns Ok(Some(line)) on Enter, Ok(None) on Ctrl+D at empty line.
-pub fn read_line_with_history(prompt: &str, history: &mut Vec<String>) -> io::Result<Option<String>> {
+/// The caller decides what goes into `history`.
+/// When stdin is not a terminal this falls back to `read_line_plain`.
+pub fn read_line_with_history(prompt: &str, history: &[String]) -> io::Result<Option<String>> {
     #[cfg(unix)]
     {
         let stdin = std::io::stdin();
+        if !stdin.is_terminal() {
+            return read_line_plain(prompt);
+        }
         let fd = stdin.as_raw_fd();
 
-        let mut orig = tcgetattr(fd).map_err(to_io)?;
+        let orig = tcgetattr(&stdin).map_err(to_io)?;
         let mut raw = orig.clone();
         // raw-ish: line-by-line, no echo, keep signals (^C)
         raw.local_flags.remove(LocalFlags::ICANON | LocalFlags::ECHO);
-        tcsetattr(fd, SetArg::TCSANOW, &raw).map_err(to_io)?;
+        tcsetattr(&stdin, SetArg::TCSANOW, &raw).map_err(to_io)?;
 
         struct Restore(i32, Termios);
         impl Drop for Restore {
-            fn drop(&mut self) { let _ = tcsetattr(self.0, SetArg::TCSANOW, &self.1); }
+            fn drop(&mut self) {
+                let fd = unsafe { std::os::fd::BorrowedFd::borrow_raw(self.0) };
+                let _ = tcsetattr(fd, SetArg::TCSANOW, &self.1);
+            }
         }
         let _restore = Restore(fd, orig.clone());
 
@@ -43,7 +51,6 @@ pub fn read_line_with_history(prompt: &str, history: &mut Vec<String>) -> io::Re
             match b[0] {
                 b'\r' | b'\n' => {
                     print!("\r\n"); std::io::stdout().flush()?;
-                    if !buf.is_empty() { history.push(buf.clone()); }
                     return Ok(Some(buf));
                 }
                 0x04 => { // Ctrl+D
@@ -105,21 +112,26 @@ pub fn read_line_with_history(prompt: &str, history: &mut Vec<String>) -> io::Re
     // Fallback (non-Unix): plain read_line (no arrows)
     #[cfg(not(unix))]
     {
+        let _ = history;
+        read_line_plain(prompt)
+    }
+}
+
+/// Buffered line reader for pipes, files and non-Unix consoles: no raw mode,
+/// no echo handling. An empty prompt prints nothing.
+pub fn read_line_plain(prompt: &str) -> io::Result<Option<String>> {
+    if !prompt.is_empty() {
         print!("{prompt}");
         std::io::stdout().flush()?;
-        let mut line = String::new();
-        let n = std::io::stdin().read_line(&mut line)?;
-        if n == 0 { print!("\n"); return Ok(None); }
-        let line = line.trim_end_matches(['\n','\r']).to_string();
-        if !line.is_empty() { history.push(line.clone()); }
-        Ok(Some(line))
     }
+    let mut line = String::new();
+    let n = std::io::stdin().lock().read_line(&mut line)?;
+    if n == 0 { return Ok(None); }
+    let line = line.trim_end_matches(['\n','\r']).to_string();
+    Ok(Some(line))
 }
 
 #[cfg(unix)]
 fn to_io(err: nix::Error) -> std::io::Error {
-    match err.as_errno() {
-        Some(e) => std::io::Error::from_raw_os_error(e as i32),
-        None => std::io::Error::new(std::io::ErrorKind::Other, err.to_string()),
-    }
+    std::io::Error::from_raw_os_error(err as i32)
 }
@@ -28,15 +28,16 @@ pub fn builtin_jobs(table: &mut JobTable, args: &[String]) -> Result<i32> {
 
     let (cur_id, prev_id) = table.current_prev_ids();
 
+    let mut out = io::stdout().lock();
     for j in table.jobs.iter() {
         if let Some(f) = filter {
             if j.state != f { continue; }
         }
         if show_pids_only {
             #[cfg(unix)]
-            { println!("{}", j.pgid.as_raw()); }
+            { writeln!(out, "{}", j.pgid.as_raw())?; }
             #[cfg(not(unix))]
-            { println!("0"); }
+            { writeln!(out, "0")?; }
             continue;
         }
 
@@ -55,11 +56,11 @@ pub fn builtin_jobs(table: &mut JobTable, args: &[String]) -> Result<i32> {
 
         if with_pid {
             #[cfg(unix)]
-            println!("[{}]{}  {:<6} {:<20} {}{}", j.id, mark, j.pgid.as_raw(), status, j.summary(), trailer);
+            writeln!(out, "[{}]{}  {:<6} {:<20} {}{}", j.id, mark, j.pgid.as_raw(), status, j.summary(), trailer)?;
             #[cfg(not(unix))]
-            println!("[{}]{}  {:<6} {:<20} {}{}", j.id, mark, 0, status, j.summary(), trailer);
+            writeln!(out, "[{}]{}  {:<6} {:<20} {}{}", j.id, mark, 0, status, j.summary(), trailer)?;
         } else {
-            println!("[{}]{}  {:<10}  {}{}", j.id, mark, status, j.summary(), trailer);
+            writeln!(out, "[{}]{}  {:<10}  {}{}", j.id, mark, status, j.summary(), trailer)?;
         }
     }
     Ok(0)
@@ -175,7 +176,7 @@ pub fn builtin_kill(table: &mut JobTable, args: &[String]) -> Result<i32> {
 
 #[cfg(unix)]
 fn resolve_job_index(table: &JobTable, args: &[String]) -> Result<usize> {
-    if let Some(tok) = args.get(0) {
+    if let Some(tok) = args.first() {
         if tok.starts_with('%') || tok == "%+" || tok == "%-" {
             if let Some(i) = table.index_by_percent(tok) {
                 return Ok(i);
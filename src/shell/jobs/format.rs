@@ -1,5 +1,6 @@
 use super::{Job, JobState};
 
+#[allow(dead_code)]
 pub fn format_jobs_simple(jobs: &[Job]) -> String {
     let mut out = String::new();
     for j in jobs {
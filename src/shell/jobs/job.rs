@@ -7,6 +7,7 @@ use nix::unistd::Pid;
 pub enum JobState {
     Running,
     Stopped,
+    #[allow(dead_code)]
     Terminated,
 }
 
@@ -78,10 +79,20 @@ impl JobTable {
         id
     }
 
+    /// Drop a finished foreground job without a "Terminated" notice.
+    #[cfg(unix)]
+    pub fn forget(&mut self, pgid: Pid) {
+        self.jobs.retain(|j| j.pgid != pgid);
+        if let Some(last) = self.jobs.back_mut() {
+            last.current = true;
+        }
+    }
+
     pub fn by_id(&mut self, id: usize) -> Option<&mut Job> {
         self.jobs.iter_mut().find(|j| j.id == id)
     }
 
+    #[allow(dead_code)]
     pub fn by_percent(&mut self, s: &str) -> Option<&mut Job> {
         // %1, %+ (current), %- (previous)
         if s == "%+" {
@@ -152,6 +163,7 @@ impl JobTable {
 pub enum UpdateKind {
     Running { pgid: Pid },
     Stopped { pgid: Pid },
+    #[allow(dead_code)]
     Terminated { pgid: Pid, code: i32 },
 }
 
@@ -4,4 +4,3 @@ pub mod notify;
 pub mod format;
 
 pub use job::*;
-pub use control::*;
@@ -6,19 +6,55 @@ pub mod exec;
 pub mod jobs;
 pub mod signals;
 pub mod scripting;
+pub mod vars;
+pub mod util;
+pub mod options;
 
 use crate::prelude::*;
 use jobs::JobTable;
-use std::collections::HashMap;
+use options::ShellOptions;
+use parser::AliasTable;
+use scripting::functions::FunctionTable;
+use signals::trap::TrapTable;
+use vars::VarStore;
 
 #[cfg(unix)]
 use nix::unistd::{getpid, Pid};
 
 pub struct Shell {
     pub last_status: i32,
-    pub vars: HashMap<String, String>,
+    pub vars: VarStore,
+    /// `$0`: the script path, or the shell's name when interactive.
+    pub arg0: String,
+    /// `$1`, `$2`, ... (`$0` is not included).
+    pub positional: Vec<String>,
     pub jobs: JobTable,
+    pub aliases: AliasTable,
+    pub functions: FunctionTable,
+    pub options: ShellOptions,
+    pub traps: TrapTable,
     pub history: Vec<String>, // if you added Up/Down support
+    /// Prompting and job control are on (stdin is a terminal, or `-i`).
+    pub interactive: bool,
+    /// Set in a forked child that runs a single simple command: an external
+    /// program replaces the child instead of forking again.
+    pub exec_in_place: bool,
+    /// Nesting depth of contexts where `set -e` is ignored (conditions,
+    /// `!`, non-final `&&`/`||` parts).
+    pub errexit_suppressed: usize,
+    /// Loops being run, for `break`/`continue` (reset inside functions).
+    pub loop_depth: usize,
+    /// Functions and sourced files being run, for `return`.
+    pub return_depth: usize,
+    /// `getopts` progress inside a cluster like `-abc`.
+    pub getopts_state: scripting::params::GetoptsState,
+    /// PATH lookups remembered for external commands (`hash`).
+    pub path_cache: exec::lookup::PathCache,
+    /// CPU time of the foreground children reaped so far (for `time`).
+    pub child_times: util::time::CpuTimes,
+    /// The shell's ends of the pipes of running process substitutions,
+    /// open until the command they were made for is done.
+    pub proc_sub_fds: Vec<i32>,
 
     #[cfg(unix)]
     pub shell_pgid: Pid,
@@ -26,24 +62,52 @@ pub struct Shell {
 
 impl Shell {
     pub fn new() -> Result<Self> {
+        Self::with_vars(VarStore::from_env())
+    }
+
+    /// A new shell whose variables start as `vars` (e.g. another shell's
+    /// exported variables).
+    pub fn with_vars(mut vars: VarStore) -> Result<Self> {
         #[cfg(unix)]
-        let shell_pgid = {
-            let pid = getpid();
-            signals::install_handlers();
-            signals::tty::take_control_of_terminal(pid).ok();
-            pid
-        };
+        let shell_pgid = getpid();
+        vars.set_scalar("OPTIND", "1");
 
         Ok(Self {
             last_status: 0,
-            vars: HashMap::new(),
+            vars,
+            arg0: "0-shell".into(),
+            positional: Vec::new(),
             jobs: JobTable::default(),
+            aliases: AliasTable::default(),
+            functions: FunctionTable::default(),
+            options: ShellOptions::default(),
+            traps: TrapTable::default(),
             history: Vec::new(),
+            interactive: false,
+            exec_in_place: false,
+            errexit_suppressed: 0,
+            loop_depth: 0,
+            return_depth: 0,
+            getopts_state: Default::default(),
+            path_cache: Default::default(),
+            child_times: Default::default(),
+            proc_sub_fds: Vec::new(),
             #[cfg(unix)]
             shell_pgid,
         })
     }
 
+    /// Switch on interactive behaviour: job-control signal handling and
+    /// ownership of the terminal. Scripts and piped input never get this.
+    pub fn enable_interactive(&mut self) {
+        self.interactive = true;
+        #[cfg(unix)]
+        {
+            signals::install_handlers();
+            signals::tty::take_control_of_terminal(self.shell_pgid).ok();
+        }
+    }
+
     pub fn repl(&mut self) -> Result<()> {
         let mut repl = repl::Repl::new();
         repl.run(self)
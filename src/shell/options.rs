@@ -0,0 +1,190 @@
+use crate::prelude::*;
+use std::collections::BTreeSet;
+
+/// Options settable with `set -o NAME` / `0-shell -o NAME`, with their
+/// single-letter forms.
+pub const OPTIONS: &[(&str, Option<char>)] = &[
+    ("errexit", Some('e')),
+    ("noclobber", Some('C')),
+    ("noexec", Some('n')),
+    ("noglob", Some('f')),
+    ("nounset", Some('u')),
+    ("pipefail", None),
+    ("verbose", Some('v')),
+    ("xtrace", Some('x')),
+];
+
+/// Options toggled with `shopt -s NAME` / `shopt -u NAME`.
+pub const SHOPT_OPTIONS: &[&str] = &["dotglob", "nullglob", "xpg_echo"];
+
+#[derive(Default, Clone)]
+pub struct ShellOptions {
+    on: BTreeSet<&'static str>,
+}
+
+impl ShellOptions {
+    pub fn get(&self, name: &str) -> bool {
+        self.on.contains(name)
+    }
+
+    pub fn set(&mut self, name: &str, on: bool) -> std::result::Result<(), String> {
+        let Some(&(name, _)) = OPTIONS.iter().find(|(n, _)| *n == name) else {
+            return Err(format!("{name}: invalid option name"));
+        };
+        if on { self.on.insert(name); } else { self.on.remove(name); }
+        Ok(())
+    }
+
+    pub fn set_shopt(&mut self, name: &str, on: bool) -> std::result::Result<(), String> {
+        let Some(&name) = SHOPT_OPTIONS.iter().find(|n| **n == name) else {
+            return Err(format!("{name}: invalid shell option name"));
+        };
+        if on { self.on.insert(name); } else { self.on.remove(name); }
+        Ok(())
+    }
+
+    /// Long name for a single-letter flag such as `e` in `-e`.
+    pub fn name_for_letter(c: char) -> Option<&'static str> {
+        OPTIONS.iter().find(|(_, l)| *l == Some(c)).map(|(n, _)| *n)
+    }
+
+    /// Letters of the active single-letter options, as shown by `$-`.
+    pub fn flags(&self) -> String {
+        OPTIONS.iter()
+            .filter(|(n, _)| self.get(n))
+            .filter_map(|(_, l)| *l)
+            .collect()
+    }
+}
+
+/// Apply the option arguments at the front of `set`'s arguments
+/// (`-eu`, `+x`, `-o name`, `+o`, `--`, `-`). Returns how many arguments were
+/// consumed and whether `--`/`-` ended them (so the rest, even if empty,
+/// replaces the positional parameters); Err(status) after a diagnostic.
+pub fn set_flags(opts: &mut ShellOptions, args: &[String]) -> std::result::Result<(usize, bool), i32> {
+    let mut i = 0;
+    while i < args.len() {
+        let a = args[i].as_str();
+        match a {
+            "--" => return Ok((i + 1, true)),
+            "-" => {
+                // `set -` turns off -x and -v and ends the options.
+                let _ = opts.set("xtrace", false);
+                let _ = opts.set("verbose", false);
+                return Ok((i + 1, true));
+            }
+            "-o" | "+o" => {
+                let on = a == "-o";
+                match args.get(i + 1) {
+                    Some(name) => {
+                        if let Err(e) = opts.set(name, on) {
+                            eprintln!("set: {e}");
+                            return Err(2);
+                        }
+                        i += 1;
+                    }
+                    None => print_options(opts, on).map_err(|_| 1)?,
+                }
+            }
+            _ if (a.starts_with('-') || a.starts_with('+')) && a.len() > 1 => {
+                let on = a.starts_with('-');
+                for c in a[1..].chars() {
+                    match ShellOptions::name_for_letter(c) {
+                        Some(name) => { let _ = opts.set(name, on); }
+                        None => {
+                            eprintln!("set: {}{c}: invalid option", &a[..1]);
+                            eprintln!("set: usage: set [-{}] [-o option] [--] [arg ...]", letters());
+                            return Err(2);
+                        }
+                    }
+                }
+            }
+            _ => break,
+        }
+        i += 1;
+    }
+    Ok((i, false))
+}
+
+fn letters() -> String {
+    OPTIONS.iter().filter_map(|(_, l)| *l).collect()
+}
+
+/// `set -o` lists every option with its state; `set +o` prints commands
+/// that restore the current settings.
+fn print_options(opts: &ShellOptions, human: bool) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    for (name, _) in OPTIONS {
+        let on = opts.get(name);
+        if human {
+            writeln!(out, "{name:<15}\t{}", if on { "on" } else { "off" })?;
+        } else {
+            writeln!(out, "set {}o {name}", if on { '-' } else { '+' })?;
+        }
+    }
+    Ok(())
+}
+
+/// shopt [-s|-u] [-p] [-q] [-o] [name ...]
+pub fn builtin_shopt(opts: &mut ShellOptions, args: &[String]) -> Result<i32> {
+    let (mut set, mut unset, mut print, mut quiet, mut set_o) = (false, false, false, false, false);
+    let mut i = 0;
+    while let Some(a) = args.get(i).filter(|a| a.starts_with('-') && a.len() > 1) {
+        if a == "--" { i += 1; break; }
+        for c in a[1..].chars() {
+            match c {
+                's' => set = true,
+                'u' => unset = true,
+                'p' => print = true,
+                'q' => quiet = true,
+                'o' => set_o = true,
+                _ => {
+                    eprintln!("shopt: -{c}: invalid option");
+                    eprintln!("shopt: usage: shopt [-pqsu] [-o] [optname ...]");
+                    return Ok(2);
+                }
+            }
+        }
+        i += 1;
+    }
+    if set && unset {
+        eprintln!("shopt: cannot set and unset shell options simultaneously");
+        return Ok(1);
+    }
+    let names: Vec<&str> = args[i..].iter().map(String::as_str).collect();
+    let known: Vec<&str> = if set_o { OPTIONS.iter().map(|(n, _)| *n).collect() } else { SHOPT_OPTIONS.to_vec() };
+
+    if set || unset {
+        let mut status = 0;
+        for name in &names {
+            let res = if set_o { opts.set(name, set) } else { opts.set_shopt(name, set) };
+            if let Err(e) = res {
+                eprintln!("shopt: {e}");
+                status = 1;
+            }
+        }
+        return Ok(status);
+    }
+
+    // Query / print.
+    let mut status = 0;
+    let mut out = io::stdout().lock();
+    let listed: Vec<&str> = if names.is_empty() { known.clone() } else { names };
+    for name in listed {
+        if !known.contains(&name) {
+            eprintln!("shopt: {name}: invalid shell option name");
+            status = 1;
+            continue;
+        }
+        let on = opts.get(name);
+        if !on { status = status.max(1); }
+        if quiet { continue; }
+        match (print, set_o) {
+            (true, true) => writeln!(out, "set {}o {name}", if on { '-' } else { '+' })?,
+            (true, false) => writeln!(out, "shopt {} {name}", if on { "-s" } else { "-u" })?,
+            _ => writeln!(out, "{name:<15}\t{}", if on { "on" } else { "off" })?,
+        }
+    }
+    // Listing everything succeeds; queries fail if any option is off.
+    Ok(if args[i..].is_empty() && !quiet { 0 } else { status })
+}
@@ -0,0 +1,222 @@
+use super::lexer::{tokenize, Op, Spanned, Token};
+use crate::prelude::*;
+use crate::shell::util::quote::single_quote;
+use std::collections::BTreeMap;
+
+#[derive(Default)]
+pub struct AliasTable {
+    map: BTreeMap<String, String>, // name -> replacement text
+    /// Bumped on every change, so a script being run knows to expand the
+    /// lines it has not parsed yet again.
+    generation: u64,
+}
+
+impl AliasTable {
+    pub fn define(&mut self, name: String, value: String) {
+        self.map.insert(name, value);
+        self.generation += 1;
+    }
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.map.get(name).map(|s| s.as_str())
+    }
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.generation += 1;
+        self.map.remove(name).is_some()
+    }
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.generation += 1;
+    }
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Replace aliases in command position. A replacement ending in a blank
+    /// makes the following word a candidate too; an alias is never expanded
+    /// again inside its own replacement.
+    pub fn expand(&self, tokens: Vec<Spanned>) -> Vec<Spanned> {
+        if self.map.is_empty() {
+            return tokens;
+        }
+        let mut out = Vec::with_capacity(tokens.len());
+        let mut state = Position::Command;
+        let mut active = Vec::new();
+        for tok in tokens {
+            state = self.step(tok.token, tok.start, state, &mut active, &mut out);
+        }
+        out
+    }
+
+    /// Handle one token starting at `start`; alias replacements are reported
+    /// at the position of the alias name.
+    fn step(&self, tok: Token, start: usize, state: Position, active: &mut Vec<String>, out: &mut Vec<Spanned>) -> Position {
+        let mut emit = |token| out.push(Spanned { token, start });
+        match tok {
+            Token::Word(w) => match state {
+                Position::Command => self.expand_word(w, start, active, out),
+                Position::RedirTarget(prev) => { emit(Token::Word(w)); *prev }
+                // `name() body`: the body is a compound command, not an alias.
+                Position::FunctionBody => {
+                    let next = keyword_position(&w).unwrap_or(Position::Argument);
+                    emit(Token::Word(w));
+                    next
+                }
+                Position::FunctionName => { emit(Token::Word(w)); Position::FunctionBody }
+                Position::Argument | Position::FunctionParens => { emit(Token::Word(w)); Position::Argument }
+            },
+            Token::Redir(..) => { emit(tok); Position::RedirTarget(Box::new(state)) }
+            Token::Op(Op::LParen) if matches!(state, Position::Argument) => { emit(tok); Position::FunctionParens }
+            Token::Op(Op::RParen) if matches!(state, Position::FunctionParens) => { emit(tok); Position::FunctionBody }
+            sep => { emit(sep); Position::Command }
+        }
+    }
+
+    /// Expand `word` into `out`; returns the position of the next word.
+    fn expand_word(&self, word: String, start: usize, active: &mut Vec<String>, out: &mut Vec<Spanned>) -> Position {
+        if let Some(next) = keyword_position(&word) {
+            out.push(Spanned { token: Token::Word(word), start });
+            return next;
+        }
+        let Some(value) = self.get(&word).filter(|_| !active.contains(&word)) else {
+            // Assignments before the command name keep us in command position.
+            let keep = super::grammar::is_assignment(&word);
+            out.push(Spanned { token: Token::Word(word), start });
+            return if keep { Position::Command } else { Position::Argument };
+        };
+
+        active.push(word);
+        let mut state = Position::Command;
+        // An unterminated replacement is passed through as plain words.
+        let tokens = tokenize(value).unwrap_or_else(|_| vec![Token::Word(value.to_string())]);
+        for tok in tokens {
+            state = self.step(tok, start, state, active, out);
+        }
+        active.pop();
+        if value.ends_with([' ', '\t']) { Position::Command } else { state }
+    }
+}
+
+/// Where the next word sits, as far as alias expansion is concerned.
+#[derive(Clone)]
+enum Position {
+    Command,
+    Argument,
+    /// The word after a redirection operator; afterwards resume the saved position.
+    RedirTarget(Box<Position>),
+    /// The name after `function`.
+    FunctionName,
+    /// Just after `name (` of a function definition.
+    FunctionParens,
+    /// Just after `name ()`.
+    FunctionBody,
+}
+
+/// Reserved words are never alias-expanded; returns the position after one.
+fn keyword_position(word: &str) -> Option<Position> {
+    match word {
+        "if" | "then" | "else" | "elif" | "while" | "until" | "do" | "{" | "!" | "time" | "coproc" => Some(Position::Command),
+        "fi" | "done" | "}" | "esac" | "for" | "case" | "select" | "in" => Some(Position::Argument),
+        "function" => Some(Position::FunctionName),
+        _ => None,
+    }
+}
+
+/// Alias names may not contain quoting or expansion characters.
+pub fn is_valid_alias_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.chars().any(|c| c.is_whitespace() || matches!(c, '/' | '$' | '`' | '=' | '\'' | '"' | '\\' | ';' | '&' | '|' | '(' | ')' | '<' | '>'))
+}
+
+/// alias [-p] [name[=value] ...]
+pub fn builtin_alias(table: &mut AliasTable, args: &[String]) -> Result<i32> {
+    let args: Vec<&String> = args.iter().filter(|a| a.as_str() != "-p").collect();
+    let mut out = io::stdout().lock();
+    if args.is_empty() {
+        for (name, value) in &table.map {
+            writeln!(out, "alias {name}={}", single_quote(value))?;
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for a in args {
+        match a.split_once('=') {
+            Some((name, value)) => {
+                if !is_valid_alias_name(name) {
+                    eprintln!("alias: `{name}': invalid alias name");
+                    status = 1;
+                    continue;
+                }
+                table.define(name.to_string(), value.to_string());
+            }
+            None => match table.get(a) {
+                Some(value) => writeln!(out, "alias {a}={}", single_quote(value))?,
+                None => {
+                    eprintln!("alias: {a}: not found");
+                    status = 1;
+                }
+            },
+        }
+    }
+    Ok(status)
+}
+
+/// unalias [-a] name ...
+pub fn builtin_unalias(table: &mut AliasTable, args: &[String]) -> Result<i32> {
+    if args.is_empty() {
+        eprintln!("unalias: usage: unalias [-a] name [name ...]");
+        return Ok(2);
+    }
+    let mut status = 0;
+    for a in args {
+        if a == "-a" {
+            table.clear();
+        } else if !table.remove(a) {
+            eprintln!("unalias: {a}: not found");
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::parser::lexer::tokenize_spanned;
+
+    /// `src` after alias expansion, tokens joined by spaces.
+    fn expanded(src: &str) -> String {
+        let mut table = AliasTable::default();
+        table.define("ll".into(), "ls -l".into());
+        table.define("s".into(), "sudo ".into());
+        let tokens = table.expand(tokenize_spanned(src).unwrap());
+        tokens.iter().map(|t| t.token.describe()).collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn only_command_words_expand() {
+        assert_eq!(expanded("ll ll"), "ls -l ll");
+        assert_eq!(expanded("x=1 ll; echo ll | ll"), "x=1 ls -l ; echo ll | ls -l");
+        assert_eq!(expanded("ll > ll && ll"), "ls -l > ll && ls -l");
+    }
+
+    #[test]
+    fn trailing_blank_expands_the_next_word() {
+        assert_eq!(expanded("s ll"), "sudo ls -l");
+    }
+
+    #[test]
+    fn function_and_case_bodies_are_command_position() {
+        assert_eq!(expanded("f() { ll; }"), "f ( ) { ls -l ; }");
+        assert_eq!(expanded("function f { ll; }"), "function f { ls -l ; }");
+        assert_eq!(expanded("function f () { ll; }"), "function f ( ) { ls -l ; }");
+        assert_eq!(expanded("case x in x) ll ;; (y) ll ;; esac"), "case x in x ) ls -l ;; ( y ) ls -l ;; esac");
+        assert_eq!(expanded("(ll)"), "( ls -l )");
+    }
+
+    #[test]
+    fn function_names_and_keywords_are_not_expanded() {
+        assert_eq!(expanded("function ll { :; }"), "function ll { : ; }");
+        assert_eq!(expanded("for ll in ll; do ll; done"), "for ll in ll ; do ls -l ; done");
+    }
+}
@@ -1,6 +1,217 @@
+use super::lexer::RedirOp;
+use std::fmt;
+use std::rc::Rc;
+
+/// Commands separated by `;`, `&` or newlines.
+pub type List = Vec<AndOr>;
+
+/// `a && b || c`, run in the background when followed by `&`.
 #[derive(Debug, Clone)]
-pub struct SimpleCommand {
-    pub cmd: String,
-    pub args: Vec<String>,
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
     pub background: bool,
+    /// Source offset of the first token (for diagnostics).
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    And,
+    Or,
+}
+
+/// `[time [-p]] [!] cmd | cmd | ...`
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    pub negated: bool,
+    pub commands: Vec<Command>,
+    /// Report how long the pipeline took (`time`); empty `commands` is allowed then.
+    pub time: Option<TimeFormat>,
+}
+
+/// Output format of `time`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// `TIMEFORMAT`, or bash's default.
+    Default,
+    /// `time -p`: POSIX `real`/`user`/`sys` lines.
+    Posix,
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Simple(SimpleCommand),
+    Compound(CompoundCommand, Vec<Redirect>),
+    FunctionDef { name: String, body: Rc<Command> },
+    /// `coproc [NAME] command`: run in the background with pipes to its
+    /// stdin/stdout kept in the array NAME (default `COPROC`).
+    Coproc { name: String, body: Box<Command> },
+}
+
+#[derive(Debug, Clone)]
+pub enum CompoundCommand {
+    Brace(List),
+    Subshell(List),
+    If { branches: Vec<(List, List)>, otherwise: Option<List> },
+    /// `while` (or `until` when `until` is set).
+    While { cond: List, body: List, until: bool },
+    /// `for var [in words]; do body; done`; no `in` means `"$@"`. With
+    /// `select` set, the words are a menu and `var` is the chosen one.
+    For { var: String, words: Option<Vec<String>>, body: List, select: bool },
+    Case { word: String, arms: Vec<CaseArm> },
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseArm {
+    pub patterns: Vec<String>,
+    pub body: List,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SimpleCommand {
+    /// Leading `NAME=value` / `NAME[i]=v` / `NAME=(...)` words.
+    pub assigns: Vec<String>,
+    /// Command name and arguments, unexpanded.
+    pub words: Vec<String>,
+    pub redirects: Vec<Redirect>,
+    /// Source offsets of `assigns` and `words`, entry for entry.
+    pub assign_offsets: Vec<usize>,
+    pub word_offsets: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    pub fd: Option<i32>,
+    pub op: RedirOp,
+    pub target: String,
+    /// Source offset of `target`.
+    pub offset: usize,
+}
+
+// ---- Source-like rendering (job summaries, `type`, xtrace) ----
+
+fn write_list(f: &mut fmt::Formatter<'_>, list: &List) -> fmt::Result {
+    for (i, item) in list.iter().enumerate() {
+        if i > 0 { write!(f, " ")?; }
+        write!(f, "{item}")?;
+        if !item.background { write!(f, ";")?; }
+    }
+    Ok(())
+}
+
+impl fmt::Display for AndOr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+        for (conn, p) in &self.rest {
+            let op = match conn { Connector::And => "&&", Connector::Or => "||" };
+            write!(f, " {op} {p}")?;
+        }
+        if self.background { write!(f, " &")?; }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.time {
+            Some(TimeFormat::Default) => write!(f, "time ")?,
+            Some(TimeFormat::Posix) => write!(f, "time -p ")?,
+            None => {}
+        }
+        if self.negated { write!(f, "! ")?; }
+        for (i, c) in self.commands.iter().enumerate() {
+            if i > 0 { write!(f, " | ")?; }
+            write!(f, "{c}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Simple(s) => write!(f, "{s}"),
+            Command::Compound(c, redirs) => {
+                write!(f, "{c}")?;
+                for r in redirs { write!(f, " {r}")?; }
+                Ok(())
+            }
+            Command::FunctionDef { name, body } => write!(f, "{name} () {body}"),
+            Command::Coproc { name, body } => write!(f, "coproc {name} {body}"),
+        }
+    }
+}
+
+impl fmt::Display for CompoundCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompoundCommand::Brace(l) => { write!(f, "{{ ")?; write_list(f, l)?; write!(f, " }}") }
+            CompoundCommand::Subshell(l) => { write!(f, "( ")?; write_list(f, l)?; write!(f, " )") }
+            CompoundCommand::If { branches, otherwise } => {
+                for (i, (cond, body)) in branches.iter().enumerate() {
+                    write!(f, "{} ", if i == 0 { "if" } else { " elif" })?;
+                    write_list(f, cond)?;
+                    write!(f, " then ")?;
+                    write_list(f, body)?;
+                }
+                if let Some(e) = otherwise {
+                    write!(f, " else ")?;
+                    write_list(f, e)?;
+                }
+                write!(f, " fi")
+            }
+            CompoundCommand::While { cond, body, until } => {
+                write!(f, "{} ", if *until { "until" } else { "while" })?;
+                write_list(f, cond)?;
+                write!(f, " do ")?;
+                write_list(f, body)?;
+                write!(f, " done")
+            }
+            CompoundCommand::For { var, words, body, select } => {
+                write!(f, "{} {var}", if *select { "select" } else { "for" })?;
+                if let Some(words) = words {
+                    write!(f, " in")?;
+                    for w in words { write!(f, " {w}")?; }
+                }
+                write!(f, "; do ")?;
+                write_list(f, body)?;
+                write!(f, " done")
+            }
+            CompoundCommand::Case { word, arms } => {
+                write!(f, "case {word} in")?;
+                for arm in arms {
+                    write!(f, " {})", arm.patterns.join(" | "))?;
+                    if !arm.body.is_empty() { write!(f, " ")?; }
+                    write_list(f, &arm.body)?;
+                    write!(f, "{}", if arm.body.is_empty() { ";;" } else { ";" })?;
+                }
+                write!(f, " esac")
+            }
+        }
+    }
+}
+
+impl fmt::Display for SimpleCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for w in self.assigns.iter().chain(&self.words) {
+            if !first { write!(f, " ")?; }
+            write!(f, "{w}")?;
+            first = false;
+        }
+        for r in &self.redirects {
+            if !first { write!(f, " ")?; }
+            write!(f, "{r}")?;
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(fd) = self.fd { write!(f, "{fd}")?; }
+        write!(f, "{}{}", self.op.as_str(), self.target)
+    }
 }
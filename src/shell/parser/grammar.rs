@@ -1,32 +1,705 @@
-use super::{ast::SimpleCommand, lexer::tokenize};
+use super::alias::AliasTable;
+use super::ast::*;
+use super::lexer::{excerpt, line_col, tokenize_partial, tokenize_spanned, Op, RedirOp, Spanned, Token};
+use std::fmt;
+use std::rc::Rc;
 
-#[derive(Debug, Clone)]
-pub struct ParsedCommand {
-    pub cmd: String,
-    pub args: Vec<String>,
-    pub background: bool,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input ends inside an unfinished construct; read more and retry.
+    Incomplete,
+    Syntax(SyntaxError),
 }
 
-// Split by ';', detect trailing '&' per command, then tokenize.
-pub fn parse_line(line: &str) -> Vec<ParsedCommand> {
-    let mut out = Vec::new();
-    for part in line.split(';') {
-        let mut s = part.trim();
-        if s.is_empty() { continue; }
+/// A syntax error and the character offset in the source it points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// e.g. `syntax error near unexpected token 'fi'`
+    pub message: String,
+    pub offset: usize,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl SyntaxError {
+    /// `src` ends inside an unfinished command (`ParseError::Incomplete`
+    /// with no more input); points just past its last non-blank character.
+    pub fn unexpected_eof(src: &str) -> Self {
+        SyntaxError { message: "syntax error: unexpected end of file".into(), offset: src.trim_end().chars().count() }
+    }
+
+    /// 1-based (line, column) of the error in `src`.
+    pub fn line_col(&self, src: &str) -> (usize, usize) {
+        line_col(src, self.offset)
+    }
+
+    /// `origin:line:col: message`, then the offending source line and a caret
+    /// under the column. `first_line` is the line number `src` starts at.
+    pub fn render(&self, origin: &str, src: &str, first_line: usize) -> String {
+        let (line, col) = self.line_col(src);
+        format!("{origin}:{}:{col}: {}\n{}", first_line + line - 1, self.message, excerpt(src, self.offset))
+    }
+}
+
+/// Words that start or continue compound commands when in command position.
+pub const RESERVED_WORDS: &[&str] = &[
+    "if", "then", "elif", "else", "fi", "while", "until", "for", "in", "do", "done",
+    "case", "esac", "{", "}", "!", "function", "coproc", "time", "select",
+];
+
+/// Expand aliases and parse `src` into a list of commands.
+pub fn parse_program(src: &str, aliases: &AliasTable) -> Result<List, ParseError> {
+    parse(src, aliases).0
+}
+
+/// `parse_program`, and whether the input ended inside the body of a
+/// compound command.
+fn parse(src: &str, aliases: &AliasTable) -> (Result<List, ParseError>, bool) {
+    let Ok(tokens) = tokenize_spanned(src) else { return (Err(ParseError::Incomplete), false) };
+    let mut p = Parser { tokens: aliases.expand(tokens), pos: 0, end: src.chars().count(), in_body: false };
+    let result = p.list(&[]).and_then(|list| match p.peek() {
+        None => Ok(list),
+        Some(_) => Err(p.unexpected_at(p.pos)),
+    });
+    (result, p.in_body)
+}
+
+/// The lines of a command being read one at a time (a script on stdin, the
+/// interactive shell), parsed again as each one arrives. A line of plain
+/// commands added inside the body of an unfinished `if`, loop or `{` can't
+/// finish it, so it skips the parse: a long body costs linear time.
+#[derive(Default)]
+pub struct Pending {
+    text: String,
+    /// The last parse ran out of input inside a compound command's body.
+    in_body: bool,
+}
+
+impl Pending {
+    /// Add `line` (ending with a newline) and parse everything read so far.
+    pub fn push_line(&mut self, line: &str, aliases: &AliasTable) -> Result<List, ParseError> {
+        let skip = self.in_body && only_simple_commands(line, aliases);
+        self.text.push_str(line);
+        if skip {
+            return Err(ParseError::Incomplete);
+        }
+        let (result, in_body) = parse(&self.text, aliases);
+        self.in_body = in_body && matches!(result, Err(ParseError::Incomplete));
+        result
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.in_body = false;
+    }
+}
+
+/// Is `line` complete on its own, with nothing but simple commands in it?
+/// Added to a body that is waiting for more commands, it leaves it waiting.
+fn only_simple_commands(line: &str, aliases: &AliasTable) -> bool {
+    let Ok(list) = parse_program(line, aliases) else { return false };
+    list.iter()
+        .flat_map(|item| std::iter::once(&item.first).chain(item.rest.iter().map(|(_, p)| p)))
+        .all(|p| p.commands.iter().all(|c| matches!(c, Command::Simple(_))))
+}
+
+/// A whole script, parsed one complete command at a time as it is run:
+/// the commands up to the end of a line, the unit a reader fed line by
+/// line would run. The source is tokenized once and every command parsed
+/// once, so a long `if` or loop costs time linear in its length.
+pub struct Script<'a> {
+    src: &'a str,
+    /// Tokens before alias expansion, for `realias`.
+    raw: Vec<Spanned>,
+    /// The source ends inside a quote: the tokens stop before it.
+    unterminated: bool,
+    parser: Parser,
+    /// Offset where the next command's lines start.
+    start: usize,
+}
+
+impl<'a> Script<'a> {
+    /// `src` must end with a newline.
+    pub fn new(src: &'a str, aliases: &AliasTable) -> Self {
+        let (raw, result) = tokenize_partial(src);
+        let parser = Parser { tokens: aliases.expand(raw.clone()), pos: 0, end: src.chars().count(), in_body: false };
+        Script { src, raw, unterminated: result.is_err(), parser, start: 0 }
+    }
+
+    /// Offset just past the lines of the commands returned so far.
+    pub fn offset(&self) -> usize {
+        self.start
+    }
+
+    /// The next complete command, None at the end of the script. A syntax
+    /// error, or input ending inside a command, is reported where a
+    /// line-by-line reader would have found it.
+    pub fn next_command(&mut self) -> Option<Result<List, SyntaxError>> {
+        let p = &mut self.parser;
+        p.skip_newlines();
+        if p.peek().is_none() {
+            return self.unterminated.then(|| Err(self.unexpected_eof()));
+        }
+        let result = p.complete_command();
+        let ended = p.pos > 0 && p.tokens.get(p.pos - 1).is_some_and(|t| t.token == Token::Newline);
+        let result = match result {
+            // Without a newline after it, the command runs into the unfinished quote.
+            Ok(list) if ended || !self.unterminated => Ok(list),
+            // Reported once a line ending after the error could be read whole.
+            Err(ParseError::Syntax(e)) if !self.unterminated || self.newline_after(e.offset) => Err(e),
+            _ => Err(self.unexpected_eof()),
+        };
+        if result.is_ok() {
+            self.start = self.parser.tokens.get(self.parser.pos - 1).map_or(self.parser.end, |t| t.start + 1);
+        }
+        Some(result)
+    }
+
+    /// The alias table changed: expand the tokens of the lines not parsed yet again.
+    pub fn realias(&mut self, aliases: &AliasTable) {
+        let first = self.raw.partition_point(|t| t.start < self.start);
+        self.parser.tokens = aliases.expand(self.raw[first..].to_vec());
+        self.parser.pos = 0;
+    }
+
+    fn newline_after(&self, offset: usize) -> bool {
+        self.raw.iter().any(|t| t.token == Token::Newline && t.start >= offset)
+    }
+
+    /// The rest of the script ends inside an unfinished command.
+    fn unexpected_eof(&self) -> SyntaxError {
+        let byte = self.src.char_indices().nth(self.start).map_or(self.src.len(), |(b, _)| b);
+        let mut e = SyntaxError::unexpected_eof(&self.src[byte..]);
+        e.offset += self.start;
+        e
+    }
+}
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    pos: usize,
+    /// Offset just past the source, where an unexpected end of line is reported.
+    end: usize,
+    /// Ran out of tokens inside a compound command's body (see `Pending`).
+    in_body: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|s| &s.token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.peek().cloned();
+        self.pos += 1;
+        t
+    }
+
+    /// Offset of token `idx`, or the end of the source.
+    fn offset(&self, idx: usize) -> usize {
+        self.tokens.get(idx).map(|s| s.start).unwrap_or(self.end)
+    }
+
+    fn syntax(&self, idx: usize, message: String) -> ParseError {
+        ParseError::Syntax(SyntaxError { message, offset: self.offset(idx) })
+    }
+
+    /// `syntax error near unexpected token 'X'` for token `idx`.
+    fn unexpected_at(&self, idx: usize) -> ParseError {
+        let what = self.tokens.get(idx).map(|s| s.token.describe()).unwrap_or_else(|| "newline".into());
+        self.syntax(idx, format!("syntax error near unexpected token '{what}'"))
+    }
+
+    fn peek_word(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Word(w)) => Some(w),
+            _ => None,
+        }
+    }
+
+    fn peek_op(&self, op: Op) -> bool {
+        self.peek() == Some(&Token::Op(op))
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Token::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume the reserved word `kw` or fail.
+    fn expect_word(&mut self, kw: &str) -> Result<(), ParseError> {
+        match self.peek() {
+            None => Err(ParseError::Incomplete),
+            Some(Token::Word(w)) if w == kw => { self.pos += 1; Ok(()) }
+            Some(_) => Err(self.unexpected_at(self.pos)),
+        }
+    }
+
+    fn expect_op(&mut self, op: Op) -> Result<(), ParseError> {
+        match self.peek() {
+            None => Err(ParseError::Incomplete),
+            Some(Token::Op(o)) if *o == op => { self.pos += 1; Ok(()) }
+            Some(_) => Err(self.unexpected_at(self.pos)),
+        }
+    }
+
+    /// Is the next token one of the `stop` words/operators that end a list?
+    fn at_stop(&self, stop: &[&str]) -> bool {
+        match self.peek() {
+            Some(Token::Word(w)) => stop.contains(&w.as_str()),
+            Some(Token::Op(op)) => stop.contains(&op.as_str()),
+            _ => false,
+        }
+    }
+
+    /// and_or items separated by `;`, `&` or newlines, up to a stop token.
+    fn list(&mut self, stop: &[&str]) -> Result<List, ParseError> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.peek().is_none() || self.at_stop(stop) {
+                break;
+            }
+            let mut item = self.and_or()?;
+            match self.peek() {
+                Some(Token::Op(Op::Semi)) | Some(Token::Newline) => self.pos += 1,
+                Some(Token::Op(Op::Amp)) => { item.background = true; self.pos += 1; }
+                _ => {
+                    items.push(item);
+                    break;
+                }
+            }
+            items.push(item);
+        }
+        Ok(items)
+    }
 
-        let mut background = false;
-        if s.ends_with('&') {
-            background = true;
-            s = s.trim_end_matches('&').trim_end();
+    /// The and_or items up to the end of the line the first one ends on.
+    fn complete_command(&mut self) -> Result<List, ParseError> {
+        let mut items = Vec::new();
+        loop {
+            let mut item = self.and_or()?;
+            match self.peek() {
+                Some(Token::Op(Op::Semi)) => self.pos += 1,
+                Some(Token::Op(Op::Amp)) => { item.background = true; self.pos += 1; }
+                Some(Token::Newline) | None => {}
+                Some(_) => return Err(self.unexpected_at(self.pos)),
+            }
+            items.push(item);
+            match self.peek() {
+                Some(Token::Newline) => {
+                    self.pos += 1;
+                    return Ok(items);
+                }
+                None => return Ok(items),
+                Some(_) => {}
+            }
         }
+    }
+
+    /// Like `list`, but at least one command is required before `stop`.
+    fn compound_list(&mut self, stop: &[&str]) -> Result<List, ParseError> {
+        let list = self.list(stop)?;
+        match self.peek() {
+            None => {
+                self.in_body = true;
+                Err(ParseError::Incomplete)
+            }
+            Some(_) if list.is_empty() => Err(self.unexpected_at(self.pos)),
+            Some(_) => Ok(list),
+        }
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, ParseError> {
+        let offset = self.offset(self.pos);
+        let first = self.pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let conn = match self.peek() {
+                Some(Token::Op(Op::AndIf)) => Connector::And,
+                Some(Token::Op(Op::OrIf)) => Connector::Or,
+                _ => break,
+            };
+            self.pos += 1;
+            self.skip_newlines();
+            rest.push((conn, self.pipeline()?));
+        }
+        Ok(AndOr { first, rest, background: false, offset })
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let mut time = None;
+        if self.peek_word() == Some("time") {
+            self.pos += 1;
+            time = Some(TimeFormat::Default);
+            if self.peek_word() == Some("-p") {
+                self.pos += 1;
+                time = Some(TimeFormat::Posix);
+            }
+        }
+        let negated = self.peek_word() == Some("!");
+        if negated {
+            self.pos += 1;
+        }
+        // A bare `time` just reports the (zero) time of nothing.
+        let nothing = matches!(self.peek(), None | Some(Token::Newline)) || matches!(self.peek(), Some(Token::Op(op)) if *op != Op::LParen);
+        if time.is_some() && nothing {
+            return Ok(Pipeline { negated, commands: Vec::new(), time });
+        }
+        let mut commands = vec![self.command()?];
+        while self.peek_op(Op::Pipe) {
+            self.pos += 1;
+            self.skip_newlines();
+            commands.push(self.command()?);
+        }
+        Ok(Pipeline { negated, commands, time })
+    }
+
+    fn command(&mut self) -> Result<Command, ParseError> {
+        let Some(tok) = self.peek().cloned() else {
+            return Err(ParseError::Incomplete);
+        };
+        let compound = match &tok {
+            Token::Op(Op::LParen) => {
+                self.pos += 1;
+                let body = self.compound_list(&[")"])?;
+                self.expect_op(Op::RParen)?;
+                CompoundCommand::Subshell(body)
+            }
+            Token::Word(w) => match w.as_str() {
+                "{" => {
+                    self.pos += 1;
+                    let body = self.compound_list(&["}"])?;
+                    self.expect_word("}")?;
+                    CompoundCommand::Brace(body)
+                }
+                "if" => self.if_clause()?,
+                "while" | "until" => {
+                    self.pos += 1;
+                    let cond = self.compound_list(&["do"])?;
+                    let body = self.do_group()?;
+                    CompoundCommand::While { cond, body, until: w == "until" }
+                }
+                "for" | "select" => self.for_clause()?,
+                "case" => self.case_clause()?,
+                "function" => {
+                    self.pos += 1;
+                    let name = match self.next() {
+                        Some(Token::Word(n)) => n,
+                        Some(_) => return Err(self.unexpected_at(self.pos - 1)),
+                        None => return Err(ParseError::Incomplete),
+                    };
+                    if self.peek_op(Op::LParen) {
+                        self.pos += 1;
+                        self.expect_op(Op::RParen)?;
+                    }
+                    return self.function_body(name);
+                }
+                "coproc" => return self.coproc(),
+                "then" | "elif" | "else" | "fi" | "do" | "done" | "esac" | "}" | "in" => {
+                    return Err(self.unexpected_at(self.pos));
+                }
+                _ => {
+                    // `name () compound-command`
+                    if self.tokens.get(self.pos + 1).map(|s| &s.token) == Some(&Token::Op(Op::LParen)) {
+                        let name = w.clone();
+                        self.pos += 2;
+                        self.expect_op(Op::RParen)?;
+                        return self.function_body(name);
+                    }
+                    return self.simple_command();
+                }
+            },
+            Token::Redir(..) => return self.simple_command(),
+            Token::Newline | Token::Op(_) => return Err(self.unexpected_at(self.pos)),
+        };
+        let redirects = self.redirects()?;
+        Ok(Command::Compound(compound, redirects))
+    }
+
+    fn function_body(&mut self, name: String) -> Result<Command, ParseError> {
+        self.skip_newlines();
+        let start = self.pos;
+        let body = self.command()?;
+        if !matches!(body, Command::Compound(..)) {
+            return Err(self.syntax(start, format!("syntax error: '{name}': function body must be a compound command")));
+        }
+        Ok(Command::FunctionDef { name, body: Rc::new(body) })
+    }
+
+    /// `coproc NAME compound-command` or `coproc command` (named COPROC).
+    fn coproc(&mut self) -> Result<Command, ParseError> {
+        self.pos += 1;
+        let starts_compound = |t: Option<&Spanned>| match t.map(|s| &s.token) {
+            Some(Token::Op(Op::LParen)) => true,
+            Some(Token::Word(w)) => matches!(w.as_str(), "{" | "if" | "while" | "until" | "for" | "select" | "case"),
+            _ => false,
+        };
+        let name = match self.peek_word() {
+            Some(w) if crate::shell::vars::is_valid_name(w) && starts_compound(self.tokens.get(self.pos + 1)) => {
+                let name = w.to_string();
+                self.pos += 1;
+                name
+            }
+            _ => "COPROC".to_string(),
+        };
+        let body = self.command()?;
+        Ok(Command::Coproc { name, body: Box::new(body) })
+    }
+
+    fn if_clause(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.pos += 1;
+        let mut branches = Vec::new();
+        let mut otherwise = None;
+        loop {
+            let cond = self.compound_list(&["then"])?;
+            self.expect_word("then")?;
+            let body = self.compound_list(&["elif", "else", "fi"])?;
+            branches.push((cond, body));
+            match self.peek_word() {
+                Some("elif") => { self.pos += 1; }
+                Some("else") => {
+                    self.pos += 1;
+                    otherwise = Some(self.compound_list(&["fi"])?);
+                    self.expect_word("fi")?;
+                    break;
+                }
+                _ => {
+                    self.expect_word("fi")?;
+                    break;
+                }
+            }
+        }
+        Ok(CompoundCommand::If { branches, otherwise })
+    }
+
+    fn do_group(&mut self) -> Result<List, ParseError> {
+        self.expect_word("do")?;
+        let body = self.compound_list(&["done"])?;
+        self.expect_word("done")?;
+        Ok(body)
+    }
 
-        let tokens = tokenize(s);
-        if tokens.is_empty() { continue; }
-        let cmd = tokens[0].clone();
-        let args = tokens[1..].to_vec();
+    fn for_clause(&mut self) -> Result<CompoundCommand, ParseError> {
+        let select = self.peek_word() == Some("select");
+        self.pos += 1;
+        let var = match self.next() {
+            Some(Token::Word(v)) if crate::shell::vars::is_valid_name(&v) => v,
+            Some(_) => return Err(self.unexpected_at(self.pos - 1)),
+            None => return Err(ParseError::Incomplete),
+        };
+        self.skip_newlines();
+        let mut words = None;
+        if self.peek_word() == Some("in") {
+            self.pos += 1;
+            let mut list = Vec::new();
+            while let Some(Token::Word(w)) = self.peek() {
+                list.push(w.clone());
+                self.pos += 1;
+            }
+            words = Some(list);
+        }
+        match self.peek() {
+            Some(Token::Op(Op::Semi)) | Some(Token::Newline) => self.pos += 1,
+            _ => {}
+        }
+        self.skip_newlines();
+        let body = self.do_group()?;
+        Ok(CompoundCommand::For { var, words, body, select })
+    }
+
+    fn case_clause(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.pos += 1;
+        let word = match self.next() {
+            Some(Token::Word(w)) => w,
+            Some(_) => return Err(self.unexpected_at(self.pos - 1)),
+            None => return Err(ParseError::Incomplete),
+        };
+        self.skip_newlines();
+        self.expect_word("in")?;
+        let mut arms = Vec::new();
+        loop {
+            self.skip_newlines();
+            match self.peek() {
+                None => return Err(ParseError::Incomplete),
+                Some(Token::Word(w)) if w == "esac" => { self.pos += 1; break; }
+                _ => {}
+            }
+            if self.peek_op(Op::LParen) {
+                self.pos += 1;
+            }
+            let mut patterns = Vec::new();
+            loop {
+                match self.next() {
+                    Some(Token::Word(p)) => patterns.push(p),
+                    Some(_) => return Err(self.unexpected_at(self.pos - 1)),
+                    None => return Err(ParseError::Incomplete),
+                }
+                if self.peek_op(Op::Pipe) {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+            self.expect_op(Op::RParen)?;
+            let body = self.list(&[";;", "esac"])?;
+            arms.push(CaseArm { patterns, body });
+            match self.peek() {
+                Some(Token::Op(Op::DSemi)) => self.pos += 1,
+                Some(Token::Word(w)) if w == "esac" => {}
+                Some(_) => return Err(self.unexpected_at(self.pos)),
+                None => {
+                    self.in_body = true;
+                    return Err(ParseError::Incomplete);
+                }
+            }
+        }
+        Ok(CompoundCommand::Case { word, arms })
+    }
+
+    /// Redirections after a compound command.
+    fn redirects(&mut self) -> Result<Vec<Redirect>, ParseError> {
+        let mut out = Vec::new();
+        while let Some(Token::Redir(fd, op)) = self.peek().cloned() {
+            self.pos += 1;
+            out.push(self.redirect(fd, op)?);
+        }
+        Ok(out)
+    }
+
+    /// The target word after a redirection operator.
+    fn redirect(&mut self, fd: Option<i32>, op: RedirOp) -> Result<Redirect, ParseError> {
+        let offset = self.offset(self.pos);
+        match self.next() {
+            Some(Token::Word(target)) => Ok(Redirect { fd, op, target, offset }),
+            _ => Err(self.unexpected_at(self.pos - 1)),
+        }
+    }
+
+    fn simple_command(&mut self) -> Result<Command, ParseError> {
+        let mut cmd = SimpleCommand::default();
+        loop {
+            match self.peek().cloned() {
+                Some(Token::Word(w)) => {
+                    let offset = self.offset(self.pos);
+                    self.pos += 1;
+                    if cmd.words.is_empty() && is_assignment(&w) {
+                        cmd.assigns.push(w);
+                        cmd.assign_offsets.push(offset);
+                    } else {
+                        cmd.words.push(w);
+                        cmd.word_offsets.push(offset);
+                    }
+                }
+                Some(Token::Redir(fd, op)) => {
+                    self.pos += 1;
+                    let redirect = self.redirect(fd, op)?;
+                    cmd.redirects.push(redirect);
+                }
+                _ => break,
+            }
+        }
+        Ok(Command::Simple(cmd))
+    }
+}
+
+/// `NAME=...`, `NAME+=...` or `NAME[sub]=...` with an unquoted name.
+pub fn is_assignment(word: &str) -> bool {
+    let Some(eq) = word.find('=') else { return false };
+    let lhs = word[..eq].strip_suffix('+').unwrap_or(&word[..eq]);
+    let name = match lhs.find('[') {
+        Some(b) if lhs.ends_with(']') => &lhs[..b],
+        Some(_) => return false,
+        None => lhs,
+    };
+    crate::shell::vars::is_valid_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The commands a `Script` hands out, one string per complete command.
+    fn commands(src: &str) -> Vec<std::result::Result<String, (String, usize)>> {
+        let aliases = AliasTable::default();
+        let mut script = Script::new(src, &aliases);
+        let mut out = Vec::new();
+        while let Some(next) = script.next_command() {
+            let stop = next.is_err();
+            out.push(next.map(|list| list.iter().map(|c| c.to_string()).collect::<Vec<_>>().join("; ")).map_err(|e| (e.message, e.offset)));
+            if stop {
+                break;
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn a_script_is_split_at_the_lines_commands_end_on() {
+        let got = commands("echo a; echo b\nif true\nthen echo c\nfi; echo d\n\n# done\n");
+        assert_eq!(got.len(), 2);
+        assert_eq!(got[0].as_deref(), Ok("echo a; echo b"));
+        assert!(got[1].as_ref().is_ok_and(|c| c.ends_with("echo d")));
+    }
+
+    #[test]
+    fn script_errors_come_after_the_commands_before_them() {
+        let got = commands("echo a\nfi\necho b\n");
+        assert_eq!(got[0].as_deref(), Ok("echo a"));
+        assert_eq!(got[1], Err(("syntax error near unexpected token 'fi'".into(), 7)));
+
+        let got = commands("echo a\nif true; then\n  echo b\n");
+        assert_eq!(got[1], Err(("syntax error: unexpected end of file".into(), 29)));
+
+        // An unclosed quote: the command it is in never finishes.
+        let got = commands("echo a\necho \"b\necho c\n");
+        assert_eq!(got[0].as_deref(), Ok("echo a"));
+        assert_eq!(got[1], Err(("syntax error: unexpected end of file".into(), 21)));
+    }
+
+    #[test]
+    fn pending_lines_parse_the_same_as_a_whole_source() {
+        let aliases = AliasTable::default();
+        let src = "while true; do\n  echo a | cat\n  x=1 && y=2\n  for i\n  echo b\ndone\n";
+        let mut pending = Pending::default();
+        let mut last = Err(ParseError::Incomplete);
+        for line in src.split_inclusive('\n') {
+            last = pending.push_line(line, &aliases);
+        }
+        let whole = parse_program(src, &aliases);
+        assert!(matches!(&last, Err(ParseError::Syntax(_))));
+        assert_eq!(last.err(), whole.err());
+    }
+
+    #[test]
+    fn unfinished_input_is_incomplete_not_an_error() {
+        let aliases = AliasTable::default();
+        for src in ["if true; then\n", "echo a |\n", "echo 'a\n", "echo a \\\n", "f() {\n", "case x in\n", "a &&\n"] {
+            assert_eq!(parse_program(src, &aliases).err(), Some(ParseError::Incomplete), "{src:?}");
+        }
+        assert!(parse_program("if true; then echo a; fi\n", &aliases).is_ok());
+    }
 
-        let _ast = SimpleCommand { cmd: cmd.clone(), args: args.clone(), background };
-        out.push(ParsedCommand { cmd, args, background });
+    #[test]
+    fn syntax_errors_point_at_the_offending_token() {
+        let aliases = AliasTable::default();
+        let src = "echo a\nif true; then\n  echo b; done\n";
+        let Err(ParseError::Syntax(e)) = parse_program(src, &aliases) else { panic!("expected a syntax error") };
+        assert_eq!(e.message, "syntax error near unexpected token 'done'");
+        assert_eq!(e.line_col(src), (3, 11));
+        assert_eq!(SyntaxError::unexpected_eof("echo (\n\n").line_col("echo (\n\n"), (1, 7));
     }
-    out
 }
@@ -1,22 +1,327 @@
-// Very small tokenizer: splits on whitespace, respects "double quotes".
-pub fn tokenize(input: &str) -> Vec<String> {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// A word exactly as written (quotes kept); expansion removes quotes later.
+    Word(String),
+    Newline,
+    Op(Op),
+    /// `[n]<`, `[n]>`, `[n]>>`, `[n]>&`, `[n]<&`; the target is the next word.
+    Redir(Option<i32>, RedirOp),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Semi,
+    DSemi,
+    Amp,
+    AndIf,
+    OrIf,
+    Pipe,
+    LParen,
+    RParen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirOp {
+    In,
+    Out,
+    /// `>|`: truncate even under `set -o noclobber`.
+    Clobber,
+    Append,
+    /// `<>`: open for reading and writing.
+    ReadWrite,
+    DupIn,
+    DupOut,
+}
+
+impl Token {
+    /// How the token reads in an error message.
+    pub fn describe(&self) -> String {
+        match self {
+            Token::Word(w) => w.clone(),
+            Token::Newline => "newline".into(),
+            Token::Op(op) => op.as_str().into(),
+            Token::Redir(_, op) => op.as_str().into(),
+        }
+    }
+}
+
+impl Op {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Op::Semi => ";",
+            Op::DSemi => ";;",
+            Op::Amp => "&",
+            Op::AndIf => "&&",
+            Op::OrIf => "||",
+            Op::Pipe => "|",
+            Op::LParen => "(",
+            Op::RParen => ")",
+        }
+    }
+}
+
+impl RedirOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RedirOp::In => "<",
+            RedirOp::Out => ">",
+            RedirOp::Clobber => ">|",
+            RedirOp::Append => ">>",
+            RedirOp::ReadWrite => "<>",
+            RedirOp::DupIn => "<&",
+            RedirOp::DupOut => ">&",
+        }
+    }
+}
+
+/// A token and the character offset where it starts in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spanned {
+    pub token: Token,
+    pub start: usize,
+}
+
+/// 1-based (line, column) of the character at `offset` in `src`.
+pub fn line_col(src: &str, offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut col = 1;
+    for c in src.chars().take(offset) {
+        if c == '\n' {
+            line += 1;
+            col = 1;
+        } else {
+            col += 1;
+        }
+    }
+    (line, col)
+}
+
+/// The source line containing `offset`, and a caret under it on the next line.
+pub fn excerpt(src: &str, offset: usize) -> String {
+    let (line, col) = line_col(src, offset);
+    let text = src.lines().nth(line - 1).unwrap_or_default();
+    // Keep tabs so the caret lines up with the source as displayed.
+    let pad: String = text.chars().take(col - 1).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+    format!("{text}\n{pad}^")
+}
+
+/// The input stopped inside a quote or right after a line-continuation `\`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unterminated;
+
+/// Tokens without positions, for callers that only need the words.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, Unterminated> {
+    Ok(tokenize_spanned(input)?.into_iter().map(|s| s.token).collect())
+}
+
+// Tokenizer: splits on whitespace and operators, keeps quoted text and
+// `${...}` together, and swallows `name=( ... )` array assignments as one word.
+pub fn tokenize_spanned(input: &str) -> Result<Vec<Spanned>, Unterminated> {
     let mut tokens = Vec::new();
+    tokenize_into(input, &mut tokens)?;
+    Ok(tokens)
+}
+
+/// Like `tokenize_spanned`, but when the input ends inside a quote (or a
+/// line continuation) also returns the tokens before the unfinished word.
+pub fn tokenize_partial(input: &str) -> (Vec<Spanned>, Result<(), Unterminated>) {
+    let mut tokens = Vec::new();
+    let result = tokenize_into(input, &mut tokens);
+    (tokens, result)
+}
+
+fn tokenize_into(input: &str, tokens: &mut Vec<Spanned>) -> Result<(), Unterminated> {
     let mut cur = String::new();
-    let mut in_quotes = false;
+    let mut word_start = 0;
+    let chars: Vec<char> = input.chars().collect();
+    let mut i = 0;
+
+    macro_rules! flush {
+        () => {
+            if !cur.is_empty() {
+                tokens.push(Spanned { token: Token::Word(std::mem::take(&mut cur)), start: word_start });
+            }
+        };
+    }
+    macro_rules! push {
+        ($tok:expr, $start:expr) => {
+            tokens.push(Spanned { token: $tok, start: $start })
+        };
+    }
 
-    for ch in input.chars() {
+    while i < chars.len() {
+        let ch = chars[i];
+        if cur.is_empty() {
+            word_start = i;
+        }
         match ch {
-            '"' => in_quotes = !in_quotes,
-            c if c.is_whitespace() && !in_quotes => {
-                if !cur.is_empty() {
-                    tokens.push(std::mem::take(&mut cur));
+            '\'' => {
+                let end = find_single_quote_end(&chars, i + 1)?;
+                cur.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+            '"' => {
+                let end = find_double_quote_end(&chars, i + 1)?;
+                cur.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+            '\\' => {
+                match chars.get(i + 1) {
+                    // Line continuation at the very end: the command goes on.
+                    Some('\n') if i + 2 >= chars.len() => return Err(Unterminated),
+                    // Line continuation: drop both characters.
+                    Some('\n') => {}
+                    Some(&next) => { cur.push('\\'); cur.push(next); }
+                    None => return Err(Unterminated),
                 }
+                i += 2;
+                continue;
+            }
+            '$' if chars.get(i + 1) == Some(&'{') => {
+                let end = find_brace_end(&chars, i + 2)?;
+                cur.extend(&chars[i..end]);
+                i = end;
+                continue;
             }
+            '(' if is_array_assign_prefix(&cur) => {
+                let end = find_paren_end(&chars, i + 1)?;
+                cur.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+            '#' if cur.is_empty() => {
+                while i < chars.len() && chars[i] != '\n' { i += 1; }
+                continue;
+            }
+            '\n' => {
+                flush!();
+                push!(Token::Newline, i);
+            }
+            ';' | '&' | '|' | '(' | ')' => {
+                flush!();
+                let next = chars.get(i + 1).copied();
+                let (op, len) = match (ch, next) {
+                    (';', Some(';')) => (Op::DSemi, 2),
+                    (';', _) => (Op::Semi, 1),
+                    ('&', Some('&')) => (Op::AndIf, 2),
+                    ('&', _) => (Op::Amp, 1),
+                    ('|', Some('|')) => (Op::OrIf, 2),
+                    ('|', _) => (Op::Pipe, 1),
+                    ('(', _) => (Op::LParen, 1),
+                    _ => (Op::RParen, 1),
+                };
+                push!(Token::Op(op), i);
+                i += len;
+                continue;
+            }
+            '<' | '>' if cur.is_empty() && chars.get(i + 1) == Some(&'(') => {
+                // Process substitution: `<(list)` / `>(list)` is one word.
+                let end = find_paren_end(&chars, i + 2)?;
+                cur.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+            '<' | '>' => {
+                // A word made only of digits right before the operator is its fd.
+                let fd = if !cur.is_empty() && cur.chars().all(|c| c.is_ascii_digit()) {
+                    cur.parse().ok().inspect(|_| cur.clear())
+                } else {
+                    None
+                };
+                let start = if fd.is_some() { word_start } else { i };
+                flush!();
+                let next = chars.get(i + 1).copied();
+                let (op, len) = match (ch, next) {
+                    ('>', Some('>')) => (RedirOp::Append, 2),
+                    ('>', Some('&')) => (RedirOp::DupOut, 2),
+                    ('>', Some('|')) => (RedirOp::Clobber, 2),
+                    ('>', _) => (RedirOp::Out, 1),
+                    ('<', Some('&')) => (RedirOp::DupIn, 2),
+                    ('<', Some('>')) => (RedirOp::ReadWrite, 2),
+                    _ => (RedirOp::In, 1),
+                };
+                push!(Token::Redir(fd, op), start);
+                i += len;
+                continue;
+            }
+            c if c.is_whitespace() => flush!(),
             _ => cur.push(ch),
         }
+        i += 1;
     }
-    if !cur.is_empty() {
-        tokens.push(cur);
+    flush!();
+    Ok(())
+}
+
+/// Index just past the closing `'`.
+fn find_single_quote_end(chars: &[char], mut i: usize) -> Result<usize, Unterminated> {
+    while i < chars.len() {
+        if chars[i] == '\'' { return Ok(i + 1); }
+        i += 1;
     }
-    tokens
+    Err(Unterminated)
+}
+
+/// Index just past the closing `"`, honouring backslash escapes and
+/// `${...}` (whose word may have quotes of its own).
+fn find_double_quote_end(chars: &[char], mut i: usize) -> Result<usize, Unterminated> {
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '$' if chars.get(i + 1) == Some(&'{') => i = find_brace_end(chars, i + 2)?,
+            '"' => return Ok(i + 1),
+            _ => i += 1,
+        }
+    }
+    Err(Unterminated)
+}
+
+/// Index just past the `}` matching an opening `${`.
+fn find_brace_end(chars: &[char], mut i: usize) -> Result<usize, Unterminated> {
+    let mut depth = 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '\'' => { i = find_single_quote_end(chars, i + 1)?; continue; }
+            '"' => { i = find_double_quote_end(chars, i + 1)?; continue; }
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 { return Ok(i + 1); }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    Err(Unterminated)
+}
+
+/// Index just past the `)` closing an array assignment or a process
+/// substitution; parentheses in between nest.
+fn find_paren_end(chars: &[char], mut i: usize) -> Result<usize, Unterminated> {
+    let mut depth = 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '\'' => { i = find_single_quote_end(chars, i + 1)?; continue; }
+            '"' => { i = find_double_quote_end(chars, i + 1)?; continue; }
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 { return Ok(i + 1); }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    Err(Unterminated)
+}
+
+/// `name=` or `name+=` directly before `(`.
+fn is_array_assign_prefix(word: &str) -> bool {
+    let Some(name) = word.strip_suffix('=') else { return false };
+    let name = name.strip_suffix('+').unwrap_or(name);
+    crate::shell::vars::is_valid_name(name)
 }
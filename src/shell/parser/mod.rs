@@ -1,5 +1,7 @@
 pub mod lexer;
 pub mod ast;
 pub mod grammar;
+pub mod alias;
 
-pub use grammar::{parse_line, ParsedCommand};
+pub use alias::AliasTable;
+pub use grammar::{parse_program, ParseError, Pending, Script, SyntaxError};
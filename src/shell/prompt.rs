@@ -2,8 +2,8 @@ use std::env;
 
 pub fn render_prompt() -> String {
     if let Ok(cwd) = env::current_dir() {
-        if let Some(name) = cwd.file_name().and_then(|s| s.to_str()) {
-            return format!("$ ");
+        if let Some(_name) = cwd.file_name().and_then(|s| s.to_str()) {
+            return "$ ".into();
         }
     }
     "$ ".into()
@@ -1,5 +1,9 @@
-use super::{exec, input::read_line_with_history, parser, prompt::render_prompt};
+use super::{exec, input::read_line_with_history, parser, prompt::render_prompt, signals::trap};
 use crate::prelude::*;
+use crate::shell::scripting::run_script_string;
+use crate::shell::util::quote::single_quote;
+use exec::status::Flow;
+use parser::ParseError;
 
 pub struct Repl;
 
@@ -7,25 +11,107 @@ impl Repl { pub fn new() -> Self { Self } }
 
 impl Repl {
     pub fn run(&mut self, shell: &mut crate::shell::Shell) -> Result<()> {
+        // Lines of a command that isn't finished yet (open quote, `if`, trailing `|`...).
+        let mut pending = parser::Pending::default();
         loop {
             // If background jobs printed, start prompt on a fresh line.
-            if exec::maybe_reap(shell) { println!(); }
+            if pending.is_empty() && exec::maybe_reap(shell) { println!(); }
+            if pending.is_empty() && exited(trap::run_pending(shell).map(|_| 0), shell) { return Ok(()); }
+            if pending.is_empty() && shell.interactive && before_prompt(shell)? { return Ok(()); }
 
-            let prompt = render_prompt(); 
-            let Some(line) = read_line_with_history(&prompt, &mut shell.history)? else {
-                println!();
+            let prompt = if !shell.interactive {
+                String::new()
+            } else if pending.is_empty() {
+                render_prompt()
+            } else {
+                shell.vars.get_str("PS2").unwrap_or("> ").to_string()
+            };
+            let Some(line) = read_line_with_history(&prompt, &shell.history)? else {
+                if shell.interactive { println!(); }
+                if !pending.text().trim().is_empty() {
+                    eprintln!("0-shell: syntax error: unexpected end of file");
+                    shell.last_status = 2;
+                }
                 break;
             };
 
-            let line = line.trim();
-            if line.is_empty() { continue; }
+            if shell.options.get("verbose") { eprintln!("{line}"); }
+            let parsed = match pending.push_line(&format!("{line}\n"), &shell.aliases) {
+                Err(ParseError::Incomplete) => continue,
+                other => other,
+            };
+
+            // The whole multi-line command is one history entry.
+            let entry = pending.text().trim_end().to_string();
+            pending.clear();
+            if entry.trim().is_empty() { continue; }
+            shell.history.push(entry.clone());
 
-            for cmd in parser::parse_line(line) {
-                let status = exec::run_parsed_command(shell, cmd)?;
-                shell.last_status = status;
-                if status == exec::status::EXIT_SIGNAL { return Ok(()); }
+            match parsed {
+                Ok(list) => {
+                    if shell.interactive && shell.functions.get("preexec").is_some() {
+                        let call = format!("preexec {}", single_quote(&entry));
+                        if run_hook(shell, "preexec", &call)? { return Ok(()); }
+                    }
+                    if exited(exec::run_list(shell, &list), shell) { return Ok(()); }
+                }
+                Err(e) => {
+                    if let ParseError::Syntax(e) = e { eprintln!("0-shell: {e}"); }
+                    shell.last_status = 2;
+                }
             }
         }
         Ok(())
     }
 }
+
+/// Did the command run `exit`? Its status becomes `$?` for the shell's exit.
+/// Any other error is reported and the session goes on.
+fn exited(result: Result<i32>, shell: &mut crate::shell::Shell) -> bool {
+    match result {
+        Ok(_) => false,
+        Err(e) => match Flow::of(&e) {
+            Some(Flow::Exit(n)) => {
+                shell.last_status = n;
+                true
+            }
+            Some(_) => false,
+            None => {
+                eprintln!("0-shell: {e}");
+                shell.last_status = 1;
+                false
+            }
+        },
+    }
+}
+
+/// Before each primary prompt: every `PROMPT_COMMAND` entry (a string or
+/// an array), then the `precmd` function. Returns true if one ran `exit`.
+fn before_prompt(shell: &mut crate::shell::Shell) -> Result<bool> {
+    let commands = shell.vars.get("PROMPT_COMMAND").map(|v| v.value.values()).unwrap_or_default();
+    for cmd in commands.iter().filter(|c| !c.trim().is_empty()) {
+        if run_hook(shell, "PROMPT_COMMAND", cmd)? { return Ok(true); }
+    }
+    if shell.functions.get("precmd").is_some() {
+        return run_hook(shell, "precmd", "precmd");
+    }
+    Ok(false)
+}
+
+/// Run hook code. `$?` is left as the user's last command set it, and a
+/// failing hook is only reported. Returns true if the hook ran `exit`.
+fn run_hook(shell: &mut crate::shell::Shell, origin: &str, src: &str) -> Result<bool> {
+    let saved = shell.last_status;
+    if let Err(e) = run_script_string(shell, origin, src) {
+        match Flow::of(&e) {
+            Some(Flow::Exit(n)) => {
+                shell.last_status = n;
+                return Ok(true);
+            }
+            Some(_) => {}
+            None => eprintln!("0-shell: {origin}: {e}"),
+        }
+    }
+    shell.last_status = saved;
+    Ok(false)
+}
@@ -1,15 +1,21 @@
+use crate::shell::parser::ast::Command;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 #[derive(Default)]
 pub struct FunctionTable {
-    pub map: HashMap<String, String>, // name -> body (raw)
+    pub map: HashMap<String, Rc<Command>>, // name -> parsed body
 }
 
 impl FunctionTable {
-    pub fn define(&mut self, name: String, body: String) {
+    pub fn define(&mut self, name: String, body: Rc<Command>) {
         self.map.insert(name, body);
     }
-    pub fn get(&self, name: &str) -> Option<&str> {
-        self.map.get(name).map(|s| s.as_str())
+    pub fn get(&self, name: &str) -> Option<Rc<Command>> {
+        self.map.get(name).cloned()
+    }
+    #[allow(dead_code)]
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.map.remove(name).is_some()
     }
 }
@@ -0,0 +1,305 @@
+use crate::shell::exec::assign::parse_lhs;
+use crate::shell::parser::ast::*;
+use crate::shell::parser::lexer::{excerpt, line_col};
+use crate::shell::parser::{parse_program, AliasTable, ParseError, SyntaxError};
+use crate::shell::util::quote::json_string;
+use crate::shell::vars::is_valid_name;
+use std::collections::HashSet;
+
+/// How `--lint` prints its findings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintFormat {
+    /// `file:line:col: warning: message [code]`, the source line and a caret.
+    Human,
+    /// One JSON object per line.
+    Json,
+}
+
+#[derive(Debug, Clone)]
+pub struct Warning {
+    /// Character offset in the script.
+    pub offset: usize,
+    /// Short stable name, e.g. `unquoted-path`.
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Commands whose operands are file names.
+const PATH_COMMANDS: &[&str] = &[
+    "cd", "ls", "cat", "cp", "mv", "rm", "mkdir", "rmdir", "touch", "source", ".", "chmod", "chown", "ln",
+];
+
+/// Lint `src` and print the findings to stdout. Returns 0 if clean, 1 if
+/// there were warnings, 2 on a syntax error.
+pub fn run_lint(origin: &str, src: &str, format: LintFormat) -> i32 {
+    let list = match parse_program(src, &AliasTable::default()) {
+        Ok(list) => list,
+        Err(e) => {
+            let e = match e {
+                ParseError::Syntax(e) => e,
+                ParseError::Incomplete => SyntaxError::unexpected_eof(src),
+            };
+            print_finding(origin, src, format, "error", e.offset, "syntax", &e.message);
+            return 2;
+        }
+    };
+    let warnings = lint(&list);
+    for w in &warnings {
+        print_finding(origin, src, format, "warning", w.offset, w.code, &w.message);
+    }
+    (!warnings.is_empty()) as i32
+}
+
+fn print_finding(origin: &str, src: &str, format: LintFormat, severity: &str, offset: usize, code: &str, message: &str) {
+    let (line, col) = line_col(src, offset);
+    match format {
+        LintFormat::Human => {
+            println!("{origin}:{line}:{col}: {severity}: {message} [{code}]");
+            println!("{}", excerpt(src, offset));
+        }
+        LintFormat::Json => println!(
+            "{{\"file\":{},\"line\":{line},\"column\":{col},\"severity\":\"{severity}\",\"code\":\"{code}\",\"message\":{}}}",
+            json_string(origin),
+            json_string(message),
+        ),
+    }
+}
+
+/// Check a parsed script; warnings come back in source order.
+pub fn lint(list: &List) -> Vec<Warning> {
+    let mut l = Linter::default();
+    l.list(list);
+
+    let mut reported = HashSet::new();
+    for (name, offset) in std::mem::take(&mut l.uses) {
+        // Upper-case names are usually environment variables set elsewhere.
+        if l.assigned.contains(&name) || !name.chars().any(|c| c.is_ascii_lowercase()) {
+            continue;
+        }
+        if reported.insert(name.clone()) {
+            l.warn(offset, "unassigned", format!("${name} is used but never assigned"));
+        }
+    }
+    l.warnings.sort_by_key(|w| w.offset);
+    l.warnings
+}
+
+#[derive(Default)]
+struct Linter {
+    warnings: Vec<Warning>,
+    assigned: HashSet<String>,
+    /// Every `$name` reference with its offset.
+    uses: Vec<(String, usize)>,
+    /// Offset used for words the parser keeps no position for (`for`/`case` words).
+    here: usize,
+}
+
+impl Linter {
+    fn warn(&mut self, offset: usize, code: &'static str, message: String) {
+        self.warnings.push(Warning { offset, code, message });
+    }
+
+    fn list(&mut self, list: &List) {
+        let mut exited = false;
+        for item in list {
+            if exited {
+                self.warn(item.offset, "unreachable", "unreachable code after `exit`".into());
+                break;
+            }
+            self.and_or(item);
+            exited = !item.background && item.rest.is_empty() && runs(&item.first, &["exit"]);
+        }
+    }
+
+    fn and_or(&mut self, item: &AndOr) {
+        self.here = item.offset;
+        let pipelines: Vec<&Pipeline> = std::iter::once(&item.first).chain(item.rest.iter().map(|(_, p)| p)).collect();
+        for (i, p) in pipelines.iter().enumerate() {
+            if let Some(offset) = cd_offset(p) {
+                let guarded = matches!(item.rest.get(i), Some((Connector::Or, next)) if runs(next, &["exit", "return"]));
+                if !guarded {
+                    self.warn(offset, "cd-without-exit", "`cd` can fail; use `cd ... || exit`".into());
+                }
+            }
+            for cmd in &p.commands {
+                self.command(cmd);
+            }
+        }
+    }
+
+    fn command(&mut self, cmd: &Command) {
+        match cmd {
+            Command::Simple(s) => self.simple(s),
+            Command::Compound(c, redirects) => {
+                self.compound(c);
+                self.redirects(redirects);
+            }
+            Command::FunctionDef { body, .. } => self.command(body),
+            Command::Coproc { name, body } => {
+                self.assigned.insert(name.clone());
+                self.assigned.insert(format!("{name}_PID"));
+                self.command(body);
+            }
+        }
+    }
+
+    fn compound(&mut self, c: &CompoundCommand) {
+        match c {
+            CompoundCommand::Brace(l) | CompoundCommand::Subshell(l) => self.list(l),
+            CompoundCommand::If { branches, otherwise } => {
+                for (cond, body) in branches {
+                    self.list(cond);
+                    self.list(body);
+                }
+                if let Some(body) = otherwise {
+                    self.list(body);
+                }
+            }
+            CompoundCommand::While { cond, body, .. } => {
+                self.list(cond);
+                self.list(body);
+            }
+            CompoundCommand::For { var, words, body, .. } => {
+                self.assigned.insert(var.clone());
+                for w in words.iter().flatten() {
+                    self.scan(w, self.here);
+                }
+                self.list(body);
+            }
+            CompoundCommand::Case { word, arms } => {
+                self.scan(word, self.here);
+                for arm in arms {
+                    self.list(&arm.body);
+                }
+            }
+        }
+    }
+
+    fn simple(&mut self, s: &SimpleCommand) {
+        for (a, &offset) in s.assigns.iter().zip(&s.assign_offsets) {
+            if let Some((lhs, _)) = parse_lhs(a) {
+                self.assigned.insert(lhs.name.to_string());
+            }
+            self.scan(a, offset);
+        }
+        for (w, &offset) in s.words.iter().zip(&s.word_offsets) {
+            self.scan(w, offset);
+        }
+        self.assignments_by_builtin(&s.words);
+
+        if s.words.first().is_some_and(|c| PATH_COMMANDS.contains(&c.as_str())) {
+            for (w, &offset) in s.words.iter().zip(&s.word_offsets).skip(1) {
+                self.unquoted_path(w, offset);
+            }
+        }
+        self.redirects(&s.redirects);
+    }
+
+    fn redirects(&mut self, redirects: &[Redirect]) {
+        for r in redirects {
+            self.scan(&r.target, r.offset);
+            self.unquoted_path(&r.target, r.offset);
+        }
+    }
+
+    /// Names set by `read`, `declare`, `export`, `getopts`, `printf -v`...
+    fn assignments_by_builtin(&mut self, words: &[String]) {
+        let Some((cmd, args)) = words.split_first() else { return };
+        let names: Vec<&String> = match cmd.as_str() {
+            "read" | "declare" | "typeset" | "local" | "export" | "readonly" | "mapfile" | "readarray" => {
+                args.iter().filter(|a| !a.starts_with('-')).collect()
+            }
+            "getopts" => args.get(1).into_iter().collect(),
+            "printf" => match args.first().map(String::as_str) {
+                Some("-v") => args.get(1).into_iter().collect(),
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        };
+        for n in names {
+            let name = n.split(['=', '[', '+']).next().unwrap_or_default();
+            if is_valid_name(name) {
+                self.assigned.insert(name.to_string());
+            }
+        }
+    }
+
+    /// Record every `$name` in `word`.
+    fn scan(&mut self, word: &str, offset: usize) {
+        for r in references(word) {
+            self.uses.push((r.name, offset + r.index));
+        }
+    }
+
+    fn unquoted_path(&mut self, word: &str, offset: usize) {
+        if let Some(r) = references(word).into_iter().find(|r| !r.quoted) {
+            let name = r.name;
+            self.warn(
+                offset + r.index,
+                "unquoted-path",
+                format!("unquoted ${name} used as a path; write \"${name}\" to avoid word splitting"),
+            );
+        }
+    }
+}
+
+/// A `$name` / `${name...}` inside a raw word.
+struct Reference {
+    name: String,
+    /// Character index of the `$`.
+    index: usize,
+    /// Inside double quotes.
+    quoted: bool,
+}
+
+fn references(word: &str) -> Vec<Reference> {
+    let chars: Vec<char> = word.chars().collect();
+    let mut out = Vec::new();
+    let mut in_double = false;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '\'' if !in_double => {
+                while i + 1 < chars.len() && chars[i + 1] != '\'' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            '"' => in_double = !in_double,
+            '$' => {
+                let mut j = i + 1;
+                if chars.get(j) == Some(&'{') {
+                    j += 1;
+                    if matches!(chars.get(j), Some('#') | Some('!')) {
+                        j += 1;
+                    }
+                }
+                let start = j;
+                while j < chars.len() && (chars[j] == '_' || chars[j].is_ascii_alphanumeric()) {
+                    j += 1;
+                }
+                let name: String = chars[start..j].iter().collect();
+                if is_valid_name(&name) {
+                    out.push(Reference { name, index: i, quoted: in_double });
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Offset of the `cd` word if `p` is a plain `cd ...` command.
+fn cd_offset(p: &Pipeline) -> Option<usize> {
+    match p.commands.as_slice() {
+        [Command::Simple(s)] if s.words.first().map(String::as_str) == Some("cd") => s.word_offsets.first().copied(),
+        _ => None,
+    }
+}
+
+/// Is `p` a single simple command named one of `names`?
+fn runs(p: &Pipeline, names: &[&str]) -> bool {
+    matches!(p.commands.as_slice(), [Command::Simple(s)] if s.words.first().is_some_and(|w| names.contains(&w.as_str())))
+}
@@ -1,5 +1,8 @@
 pub mod runner;
+pub mod source;
+pub mod params;
+pub mod functions;
+pub mod lint;
 
 pub use runner::run_script_file;
-// (optional)
-// pub use runner::run_script_string;
+pub use runner::run_script_string;
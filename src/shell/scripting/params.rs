@@ -0,0 +1,189 @@
+use crate::prelude::*;
+use crate::shell::exec::declare::format_assignment;
+use crate::shell::options::set_flags;
+use crate::shell::vars::is_valid_name;
+use crate::shell::Shell;
+
+/// shift [n]: drop the first n positional parameters (default 1).
+pub fn builtin_shift(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let n = match args.first() {
+        None => 1,
+        Some(a) => match a.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => {
+                eprintln!("shift: {a}: numeric argument required");
+                return Ok(1);
+            }
+        },
+    };
+    if n > shell.positional.len() {
+        return Ok(1);
+    }
+    shell.positional.drain(..n);
+    Ok(0)
+}
+
+/// Where `getopts` stopped: the OPTIND it last set, the argument there and
+/// the character position inside it. Any assignment to OPTIND resets it.
+#[derive(Debug, Clone, Default)]
+pub struct GetoptsState {
+    optind: usize,
+    arg: String,
+    pos: usize,
+}
+
+/// getopts OPTSTRING NAME [ARGS...]: parse the next option from ARGS (the
+/// positional parameters by default) into NAME, with its argument in OPTARG.
+/// OPTIND is the index of the next argument; status 1 when options run out.
+pub fn builtin_getopts(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let [optstring, name, rest @ ..] = args else {
+        eprintln!("getopts: usage: getopts optstring name [arg ...]");
+        return Ok(2);
+    };
+    if !is_valid_name(name) {
+        eprintln!("getopts: `{name}': not a valid identifier");
+        return Ok(1);
+    }
+    let argv: Vec<String> = if rest.is_empty() { shell.positional.clone() } else { rest.to_vec() };
+    let (silent, spec) = match optstring.strip_prefix(':') {
+        Some(spec) => (true, spec),
+        None => (false, optstring.as_str()),
+    };
+    let quiet = silent || shell.vars.get_str("OPTERR") == Some("0");
+
+    let mut optind = shell.vars.get_str("OPTIND").and_then(|s| s.parse::<usize>().ok()).filter(|&n| n > 0).unwrap_or(1);
+    let Some(word) = argv.get(optind - 1) else {
+        return Ok(end_of_options(shell, name, optind));
+    };
+    let arg: Vec<char> = word.chars().collect();
+    // Position inside a clustered argument like `-abc`; starts over when
+    // OPTIND or the argument there changed since the last call.
+    let state = &shell.getopts_state;
+    let mut pos = if state.optind == optind && state.arg == *word && (1..arg.len()).contains(&state.pos) {
+        state.pos
+    } else {
+        1
+    };
+    if pos == 1 && (arg.len() < 2 || arg[0] != '-') {
+        return Ok(end_of_options(shell, name, optind));
+    }
+    if pos == 1 && arg == ['-', '-'] {
+        return Ok(end_of_options(shell, name, optind + 1));
+    }
+
+    let c = arg[pos];
+    pos += 1;
+    if pos >= arg.len() {
+        optind += 1;
+        pos = 1;
+    }
+    let found = spec.find(c).filter(|_| c != ':');
+    let takes_arg = found.is_some_and(|i| spec[i + c.len_utf8()..].starts_with(':'));
+
+    let (value, optarg) = if found.is_none() {
+        if !quiet {
+            eprintln!("{}: illegal option -- {c}", shell.arg0);
+        }
+        ("?".to_string(), silent.then(|| c.to_string()))
+    } else if !takes_arg {
+        (c.to_string(), None)
+    } else if pos > 1 {
+        // The rest of this argument: `-bfoo`.
+        let value: String = arg[pos..].iter().collect();
+        optind += 1;
+        pos = 1;
+        (c.to_string(), Some(value))
+    } else if let Some(value) = argv.get(optind - 1) {
+        optind += 1;
+        (c.to_string(), Some(value.clone()))
+    } else if silent {
+        (":".to_string(), Some(c.to_string()))
+    } else {
+        if !quiet {
+            eprintln!("{}: option requires an argument -- {c}", shell.arg0);
+        }
+        ("?".to_string(), None)
+    };
+
+    match optarg {
+        Some(v) => shell.vars.set_scalar("OPTARG", v),
+        None => shell.vars.unset("OPTARG"),
+    }
+    shell.vars.set_scalar(name, value);
+    shell.vars.set_scalar("OPTIND", optind.to_string());
+    shell.getopts_state = match argv.get(optind - 1) {
+        Some(next) if pos > 1 => GetoptsState { optind, arg: next.clone(), pos },
+        _ => GetoptsState::default(),
+    };
+    Ok(0)
+}
+
+/// No more options: NAME is `?` and OPTIND points at the first operand.
+fn end_of_options(shell: &mut Shell, name: &str, optind: usize) -> i32 {
+    shell.vars.set_scalar(name, "?");
+    shell.vars.set_scalar("OPTIND", optind.to_string());
+    shell.getopts_state = GetoptsState::default();
+    1
+}
+
+/// set [-eux...] [-o option] [--] [args...]: change shell options and/or
+/// replace the positional parameters; with no arguments, list shell variables.
+pub fn builtin_set(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    if args.is_empty() {
+        let mut out = io::stdout().lock();
+        for name in shell.vars.names() {
+            if let Some(var) = shell.vars.get(&name) {
+                writeln!(out, "{}", format_assignment(&name, &var.value))?;
+            }
+        }
+        return Ok(0);
+    }
+    let (used, ended) = match set_flags(&mut shell.options, args) {
+        Ok(r) => r,
+        Err(status) => return Ok(status),
+    };
+    if ended || used < args.len() {
+        shell.positional = args[used..].to_vec();
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::scripting::run_script_string;
+    use crate::shell::vars::VarStore;
+
+    fn run(src: &str) -> Shell {
+        let mut shell = Shell::with_vars(VarStore::default()).unwrap();
+        run_script_string(&mut shell, "test", src).unwrap();
+        shell
+    }
+
+    fn var<'a>(shell: &'a Shell, name: &str) -> &'a str {
+        shell.vars.get_str(name).unwrap_or_default()
+    }
+
+    #[test]
+    fn optind_reset_inside_a_cluster_starts_over() {
+        let sh = run("getopts abc o -abc; OPTIND=1; getopts x o -x");
+        assert_eq!((var(&sh, "o"), var(&sh, "OPTIND")), ("x", "2"));
+
+        let sh = run("getopts abc o -abc; OPTIND=1; getopts abc o -abc");
+        assert_eq!((var(&sh, "o"), var(&sh, "OPTIND")), ("a", "1"));
+    }
+
+    #[test]
+    fn a_different_argument_starts_over() {
+        let sh = run("getopts ab o -ab; getopts ab o -x");
+        assert_eq!(var(&sh, "o"), "?");
+        assert_eq!(var(&sh, "OPTIND"), "2");
+    }
+
+    #[test]
+    fn clusters_and_option_arguments() {
+        let sh = run("r=; while getopts ab:c o -ac -bfoo -c x; do r=$r$o${OPTARG-}; done");
+        assert_eq!(var(&sh, "r"), "acbfooc");
+        assert_eq!(var(&sh, "OPTIND"), "4");
+    }
+}
@@ -1,27 +1,197 @@
 use crate::prelude::*;
+use crate::shell::exec::{self, read::FdInput};
+use crate::shell::parser::{self, ParseError, SyntaxError};
+use crate::shell::Shell;
 use std::fs;
 use std::path::Path;
 
-pub fn run_script_file(shell: &mut crate::shell::Shell, path: &Path) -> Result<i32> {
+#[cfg(feature = "external")]
+use crate::shell::{exec::status, signals::trap, vars::VarStore};
+#[cfg(feature = "external")]
+use std::io::{BufRead, Read};
+
+pub fn run_script_file(shell: &mut Shell, path: &Path) -> Result<i32> {
     let src = fs::read_to_string(path)?;
-    run_script_string(shell, &src)
+    run_script_string(shell, &path.display().to_string(), &src)
+}
+
+/// Run `path` in a new shell that starts like a new 0-shell process would:
+/// only the exported variables, `$0` = `name` and `args` as `$1`, `$2`...
+/// Used for executables without a usable `#!` line (ENOEXEC) and for
+/// `#!` lines naming 0-shell. Returns the exit status.
+#[cfg(feature = "external")]
+pub fn run_in_fresh_shell(shell: &Shell, path: &Path, name: &str, args: &[String]) -> i32 {
+    let mut fresh = match Shell::with_vars(VarStore::from_pairs(shell.vars.exported_env())) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("0-shell: {e}");
+            return 126;
+        }
+    };
+    fresh.arg0 = name.to_string();
+    fresh.positional = args.to_vec();
+    let last = fresh.last_status;
+    let status = match status::exit_status(run_script_file(&mut fresh, path), last) {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("0-shell: {name}: {e}");
+            126
+        }
+    };
+    fresh.last_status = status;
+    let status = status::exit_status(trap::run_exit(&mut fresh).map(|_| status), status).unwrap_or(status);
+    let _ = io::stdout().flush();
+    status
+}
+
+/// Does `path` start with a `#!` line for this shell (`#!/usr/local/bin/0-shell`,
+/// `#!/usr/bin/env 0-shell`, or the running binary's own path)?
+#[cfg(feature = "external")]
+pub fn has_own_shebang(path: &Path) -> bool {
+    let Ok(file) = fs::File::open(path) else { return false };
+    let mut first = String::new();
+    if io::BufReader::new(file).take(256).read_line(&mut first).is_err() {
+        return false;
+    }
+    let Some(rest) = first.strip_prefix("#!") else { return false };
+    let mut words = rest.split_whitespace();
+    let mut interp = Path::new(words.next().unwrap_or_default());
+    if interp.file_name().is_some_and(|n| n == "env") {
+        interp = Path::new(words.next().unwrap_or_default());
+    }
+    let own_name = interp.file_name().is_some_and(|n| n == "0-shell" || n == "zero-shell");
+    own_name || std::env::current_exe().is_ok_and(|exe| exe == interp)
 }
 
-#[allow(dead_code)]
-pub fn run_script_string(shell: &mut crate::shell::Shell, src: &str) -> Result<i32> {
+/// Run `src` one complete command at a time, so aliases defined on one
+/// line apply to the next. `origin` names the source in diagnostics.
+/// Returns the last status, or 2 on a syntax error; `exit` in the script
+/// comes back as a `Flow` error so callers stop too.
+pub fn run_script_string(shell: &mut Shell, origin: &str, src: &str) -> Result<i32> {
+    let src = if src.ends_with('\n') { src.to_string() } else { format!("{src}\n") };
+    let mut script = parser::Script::new(&src, &shell.aliases);
+    let mut echo = Echo::default();
     let mut last = 0;
-    for (lineno, raw) in src.lines().enumerate() {
-        let line = raw.trim();
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
-        // very simple: support ';' chaining already handled by parser
-        for cmd in crate::shell::parser::parse_line(line) {
-            last = crate::shell::exec::run_parsed_command(shell, cmd)?;
-            if last == crate::shell::exec::status::EXIT_SIGNAL {
-                return Ok(0);
+    loop {
+        match script.next_command() {
+            None => {
+                echo.upto(shell, &src, usize::MAX);
+                return Ok(last);
+            }
+            Some(Err(e)) => {
+                echo.upto(shell, &src, e.offset);
+                eprintln!("{}", e.render(origin, &src, 1));
+                shell.last_status = 2;
+                return Ok(2);
+            }
+            Some(Ok(list)) => {
+                echo.upto(shell, &src, script.offset());
+                // `-n`: check syntax only (ignored by interactive shells, as in bash).
+                if list.is_empty() || (shell.options.get("noexec") && !shell.interactive) {
+                    continue;
+                }
+                let aliases = shell.aliases.generation();
+                last = exec::run_list(shell, &list)?;
+                if shell.aliases.generation() != aliases {
+                    script.realias(&shell.aliases);
+                }
+            }
+        }
+    }
+}
+
+/// `set -v`: the source lines echoed so far, as a byte index and a
+/// character offset into the script.
+#[derive(Default)]
+struct Echo {
+    byte: usize,
+    offset: usize,
+}
+
+impl Echo {
+    /// Pass the lines up to the one holding character `offset` (all of them
+    /// for `usize::MAX`), echoing them if `set -v` is on.
+    fn upto(&mut self, shell: &Shell, src: &str, offset: usize) {
+        let verbose = shell.options.get("verbose");
+        while self.byte < src.len() && self.offset < offset {
+            let line = src[self.byte..].split_inclusive('\n').next().unwrap_or_default();
+            if verbose {
+                eprintln!("{}", line.trim_end_matches('\n'));
             }
+            self.byte += line.len();
+            self.offset += line.chars().count();
+        }
+    }
+}
+
+/// Run a script piped on stdin (`cat gen.sh | 0-shell`). Lines are read
+/// unbuffered, one at a time, so builtins like `read` see the rest of the input.
+pub fn run_script_stdin(shell: &mut Shell) -> Result<i32> {
+    let mut input = FdInput::new(0);
+    let mut reader = Reader::new("stdin");
+    while let Some((line, _)) = input.read_until(b'\n')? {
+        if let Some(status) = reader.feed(shell, &String::from_utf8_lossy(&line))? {
+            return Ok(status);
+        }
+    }
+    reader.finish(shell)
+}
+
+/// Collects source lines until they parse as complete commands, then runs them.
+struct Reader<'a> {
+    origin: &'a str,
+    pending: parser::Pending,
+    /// Line number of the first line in `buf`.
+    first_line: usize,
+    lines_read: usize,
+    last: i32,
+}
+
+impl<'a> Reader<'a> {
+    fn new(origin: &'a str) -> Self {
+        Self { origin, pending: parser::Pending::default(), first_line: 1, lines_read: 0, last: 0 }
+    }
+
+    /// Add one line; returns Some(2) when the script must stop after a
+    /// syntax error.
+    fn feed(&mut self, shell: &mut Shell, line: &str) -> Result<Option<i32>> {
+        // `set -v`: echo input as it is read.
+        if shell.options.get("verbose") {
+            eprintln!("{}", line.trim_end_matches('\n'));
+        }
+        let parsed = if line.ends_with('\n') {
+            self.pending.push_line(line, &shell.aliases)
+        } else {
+            self.pending.push_line(&format!("{line}\n"), &shell.aliases)
+        };
+        self.lines_read += 1;
+        let list = match parsed {
+            Err(ParseError::Incomplete) => return Ok(None),
+            Err(ParseError::Syntax(e)) => {
+                eprintln!("{}", e.render(self.origin, self.pending.text(), self.first_line));
+                shell.last_status = 2;
+                return Ok(Some(2));
+            }
+            Ok(list) => list,
+        };
+        self.pending.clear();
+        self.first_line = self.lines_read + 1;
+        // `-n`: check syntax only (ignored by interactive shells, as in bash).
+        if list.is_empty() || (shell.options.get("noexec") && !shell.interactive) {
+            return Ok(None);
+        }
+        self.last = exec::run_list(shell, &list)?;
+        Ok(None)
+    }
+
+    /// Input ended; anything left in the buffer is an unfinished command.
+    fn finish(self, shell: &mut Shell) -> Result<i32> {
+        let text = self.pending.text();
+        if text.trim().is_empty() {
+            return Ok(self.last);
         }
+        eprintln!("{}", SyntaxError::unexpected_eof(text).render(self.origin, text, self.first_line));
+        shell.last_status = 2;
+        Ok(2)
     }
-    Ok(last)
 }
@@ -0,0 +1,56 @@
+use super::run_script_string;
+use crate::prelude::*;
+use crate::shell::exec::catch_return;
+use crate::shell::signals::trap::{self, Trap};
+use crate::shell::Shell;
+use std::fs;
+use std::path::PathBuf;
+
+/// source / . FILE [ARGS...]: run FILE in the current shell. A name without
+/// a slash is looked up in PATH first, then in the current directory.
+pub fn builtin_source(shell: &mut Shell, name: &str, args: &[String]) -> Result<i32> {
+    let Some(file) = args.first() else {
+        eprintln!("{name}: filename argument required");
+        eprintln!("{name}: usage: {name} filename [arguments]");
+        return Ok(2);
+    };
+    let path = find_sourced_file(shell, file);
+    let src = match fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{name}: {file}: {e}");
+            return Ok(1);
+        }
+    };
+
+    // Extra arguments replace the positional parameters for the duration.
+    let saved = (args.len() > 1).then(|| std::mem::replace(&mut shell.positional, args[1..].to_vec()));
+    shell.return_depth += 1;
+    let status = run_script_string(shell, file, &src);
+    shell.return_depth -= 1;
+    if let Some(saved) = saved {
+        shell.positional = saved;
+    }
+    let status = catch_return(status)?;
+    shell.last_status = status;
+    trap::run(shell, Trap::Return)?;
+    Ok(status)
+}
+
+fn find_sourced_file(shell: &Shell, file: &str) -> PathBuf {
+    if !file.contains('/') {
+        let path = shell.vars.get_str("PATH").unwrap_or_default();
+        for dir in path.split(':').filter(|d| !d.is_empty()) {
+            let cand = PathBuf::from(dir).join(file);
+            if cand.is_file() {
+                return cand;
+            }
+        }
+    }
+    PathBuf::from(file)
+}
+
+/// eval [ARGS...]: join the arguments with spaces and run the result.
+pub fn builtin_eval(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    run_script_string(shell, "eval", &args.join(" "))
+}
@@ -1,5 +1,5 @@
 #[cfg(unix)]
-use nix::sys::signal::{SigHandler, SigSet, Signal};
+use nix::sys::signal::{SigHandler, Signal};
 #[cfg(unix)]
 use once_cell::sync::Lazy;
 #[cfg(unix)]
@@ -16,15 +16,59 @@ extern "C" fn on_sigchld(_sig: i32) {
 #[cfg(unix)]
 pub fn install_handlers() {
     unsafe {
-        let _ = nix::sys::signal::signal(Signal::SIGCHLD, SigHandler::Handler(on_sigchld));
+        for sig in [Signal::SIGCHLD, Signal::SIGINT, Signal::SIGTSTP, Signal::SIGTTOU] {
+            let _ = nix::sys::signal::signal(sig, base_handler(sig, true));
+        }
+    }
+}
+
+/// The shell's own disposition for `sig` when no trap is set.
+#[cfg(unix)]
+pub fn base_handler(sig: Signal, interactive: bool) -> SigHandler {
+    match sig {
+        Signal::SIGCHLD if interactive => SigHandler::Handler(on_sigchld),
         // Ignore SIGINT in the shell; foreground jobs get the signal
-        let _ = nix::sys::signal::signal(Signal::SIGINT, SigHandler::SigIgn);
+        Signal::SIGINT if interactive => SigHandler::SigIgn,
         // Shell shouldn't stop on Ctrl+Z; the foreground job should
-        let _ = nix::sys::signal::signal(Signal::SIGTSTP, SigHandler::SigIgn);
+        Signal::SIGTSTP if interactive => SigHandler::SigIgn,
+        // Taking the terminal back with tcsetpgrp from the background must not stop us
+        Signal::SIGTTOU if interactive => SigHandler::SigIgn,
+        _ => SigHandler::SigDfl,
     }
 }
 
+/// Undo `install_handlers` in a forked child so jobs get default
+/// Ctrl+C / Ctrl+Z behaviour.
+#[cfg(unix)]
+pub fn reset_for_child() {
+    unsafe {
+        for sig in [Signal::SIGCHLD, Signal::SIGINT, Signal::SIGTSTP, Signal::SIGTTOU] {
+            let _ = nix::sys::signal::signal(sig, SigHandler::SigDfl);
+        }
+    }
+}
+
+/// SIGPIPE for a program about to be exec'd: the default (die quietly on a
+/// closed pipe) unless `trap '' PIPE` ignored it. The Rust runtime starts
+/// the shell with it ignored, and exec keeps ignored signals ignored.
+#[cfg(all(unix, feature = "external"))]
+pub fn sigpipe_for_exec(traps: &trap::TrapTable) {
+    let handler = match traps.get(trap::Trap::Signal(Signal::SIGPIPE as i32)) {
+        Some("") => SigHandler::SigIgn,
+        _ => SigHandler::SigDfl,
+    };
+    let _ = unsafe { nix::sys::signal::signal(Signal::SIGPIPE, handler) };
+}
+
+/// Back to the shell's own SIGPIPE disposition after a failed exec: ignored,
+/// so writes to a closed pipe fail with EPIPE instead of killing the shell.
+#[cfg(all(unix, feature = "external"))]
+pub fn sigpipe_for_shell() {
+    let _ = unsafe { nix::sys::signal::signal(Signal::SIGPIPE, SigHandler::SigIgn) };
+}
+
 #[cfg(not(unix))]
 pub fn install_handlers() {}
 
+pub mod trap;
 pub mod tty;
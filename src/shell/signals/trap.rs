@@ -0,0 +1,268 @@
+use crate::prelude::*;
+use crate::shell::scripting::run_script_string;
+use crate::shell::Shell;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(unix)]
+use nix::sys::signal::{SigHandler, Signal};
+
+/// What a trap is attached to, in the order `trap -p` lists them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Trap {
+    /// The shell is exiting.
+    Exit,
+    Signal(i32),
+    /// Before each simple command.
+    Debug,
+    /// After a command fails (same rules as `set -e`).
+    Err,
+    /// After a function or sourced file returns.
+    Return,
+}
+
+impl Trap {
+    /// Parse `INT`, `SIGINT`, `int`, `2`, `EXIT`, `0`, `ERR`...
+    pub fn parse(spec: &str) -> Option<Trap> {
+        if let Ok(n) = spec.parse::<i32>() {
+            return match n {
+                0 => Some(Trap::Exit),
+                #[cfg(unix)]
+                _ => Signal::try_from(n).ok().map(|_| Trap::Signal(n)),
+                #[cfg(not(unix))]
+                _ => None,
+            };
+        }
+        let upper = spec.to_ascii_uppercase();
+        match upper.as_str() {
+            "EXIT" => return Some(Trap::Exit),
+            "DEBUG" => return Some(Trap::Debug),
+            "ERR" => return Some(Trap::Err),
+            "RETURN" => return Some(Trap::Return),
+            _ => {}
+        }
+        #[cfg(unix)]
+        {
+            let name = if upper.starts_with("SIG") { upper } else { format!("SIG{upper}") };
+            Signal::iterator().find(|s| s.as_str() == name).map(|s| Trap::Signal(s as i32))
+        }
+        #[cfg(not(unix))]
+        None
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Trap::Exit => "EXIT".into(),
+            Trap::Debug => "DEBUG".into(),
+            Trap::Err => "ERR".into(),
+            Trap::Return => "RETURN".into(),
+            #[cfg(unix)]
+            Trap::Signal(n) => Signal::try_from(*n).map(|s| s.as_str().to_string()).unwrap_or_else(|_| n.to_string()),
+            #[cfg(not(unix))]
+            Trap::Signal(n) => n.to_string(),
+        }
+    }
+}
+
+/// Signals caught since the executor last looked, one bit per signal number.
+static PENDING: AtomicU64 = AtomicU64::new(0);
+
+/// Handler for trapped signals: only note the signal; the action runs
+/// later, between commands, where the shell is in a consistent state.
+#[cfg(unix)]
+extern "C" fn on_trapped(sig: i32) {
+    PENDING.fetch_or(1 << sig, Ordering::SeqCst);
+    if sig == Signal::SIGCHLD as i32 {
+        super::CHLD_FLAG.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Trap actions set with `trap`. An empty action means the signal is ignored.
+#[derive(Default)]
+pub struct TrapTable {
+    actions: BTreeMap<Trap, String>,
+    /// While an action runs: `$?` from before it. DEBUG/ERR/RETURN don't
+    /// fire inside an action.
+    running: Option<i32>,
+}
+
+impl TrapTable {
+    pub fn get(&self, trap: Trap) -> Option<&str> {
+        self.actions.get(&trap).map(String::as_str)
+    }
+
+    /// `$?` from before the trap action that is running, if any (the
+    /// status `exit` without an argument uses there).
+    pub fn status_before(&self) -> Option<i32> {
+        self.running
+    }
+
+    /// Set `trap`'s action, or reset it with None. Signal dispositions are
+    /// updated to match; `interactive` decides what "reset" restores.
+    pub fn set(&mut self, trap: Trap, action: Option<String>, interactive: bool) {
+        #[cfg(unix)]
+        if let Trap::Signal(n) = trap {
+            if let Ok(sig) = Signal::try_from(n) {
+                let handler = match action.as_deref() {
+                    None => super::base_handler(sig, interactive),
+                    Some("") => SigHandler::SigIgn,
+                    Some(_) => SigHandler::Handler(on_trapped),
+                };
+                // SIGKILL/SIGSTOP can't be caught; bash accepts the trap anyway.
+                let _ = unsafe { nix::sys::signal::signal(sig, handler) };
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = interactive;
+        match action {
+            Some(a) => { self.actions.insert(trap, a); }
+            None => { self.actions.remove(&trap); }
+        }
+    }
+
+    /// In a forked subshell: caught signals go back to their default and
+    /// their actions are forgotten; ignored signals stay ignored.
+    pub fn reset_for_subshell(&mut self) {
+        PENDING.store(0, Ordering::SeqCst);
+        let caught: Vec<Trap> = self.actions.iter().filter(|(_, a)| !a.is_empty()).map(|(t, _)| *t).collect();
+        for trap in caught {
+            self.set(trap, None, false);
+        }
+        self.reapply_ignored();
+    }
+
+    /// Ignore again the signals trapped with an empty action, after
+    /// `signals::reset_for_child` put job-control signals back to default.
+    pub fn reapply_ignored(&self) {
+        #[cfg(unix)]
+        for (trap, _) in self.actions.iter().filter(|(_, a)| a.is_empty()) {
+            if let Trap::Signal(n) = trap {
+                if let Ok(sig) = Signal::try_from(*n) {
+                    let _ = unsafe { nix::sys::signal::signal(sig, SigHandler::SigIgn) };
+                }
+            }
+        }
+    }
+}
+
+/// Run `trap`'s action, if any. `$?` is left as it was, and `exit` without
+/// a status inside the action exits with the status from before the trap.
+/// `exit` (or `return`) in the action comes back as a `Flow` error.
+pub fn run(shell: &mut Shell, trap: Trap) -> Result<()> {
+    let Some(action) = shell.traps.get(trap).filter(|a| !a.is_empty()).map(str::to_string) else {
+        return Ok(());
+    };
+    if shell.traps.running.is_some() && !matches!(trap, Trap::Exit | Trap::Signal(_)) {
+        return Ok(());
+    }
+    let saved = shell.last_status;
+    let was_running = shell.traps.running.replace(saved);
+    let status = run_script_string(shell, "trap", &action);
+    shell.traps.running = was_running;
+    shell.last_status = saved;
+    status.map(|_| ())
+}
+
+/// Run the actions of signals caught since the last call.
+pub fn run_pending(shell: &mut Shell) -> Result<()> {
+    let mut pending = PENDING.swap(0, Ordering::SeqCst);
+    while pending != 0 {
+        let n = pending.trailing_zeros() as i32;
+        pending &= pending - 1;
+        run(shell, Trap::Signal(n))?;
+    }
+    Ok(())
+}
+
+/// Run the EXIT trap once, as the shell (or a subshell) ends.
+pub fn run_exit(shell: &mut Shell) -> Result<()> {
+    let status = run(shell, Trap::Exit);
+    shell.traps.actions.remove(&Trap::Exit);
+    status
+}
+
+/// trap [-lp] [[action] signal ...]
+pub fn builtin_trap(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut print = false;
+    let mut i = 0;
+    while let Some(a) = args.get(i).filter(|a| a.starts_with('-') && a.len() > 1) {
+        match a.as_str() {
+            "--" => { i += 1; break; }
+            "-l" => return list_signals(),
+            "-p" => print = true,
+            _ => {
+                eprintln!("trap: {a}: invalid option");
+                eprintln!("trap: usage: trap [-lp] [[action] signal_spec ...]");
+                return Ok(2);
+            }
+        }
+        i += 1;
+    }
+    let args = &args[i..];
+    if print || args.is_empty() {
+        return print_traps(&shell.traps, args);
+    }
+
+    // `trap INT` and `trap 2 15` (no action) reset, like `trap - INT`.
+    let (action, specs) = match args {
+        [first, rest @ ..] if first == "-" => (None, rest),
+        [_] => (None, args),
+        [first, ..] if first.parse::<u32>().is_ok() => (None, args),
+        [first, rest @ ..] => (Some(first.clone()), rest),
+        [] => unreachable!(),
+    };
+    let mut status = 0;
+    for spec in specs {
+        match Trap::parse(spec) {
+            Some(trap) => shell.traps.set(trap, action.clone(), shell.interactive),
+            None => {
+                eprintln!("trap: {spec}: invalid signal specification");
+                status = 1;
+            }
+        }
+    }
+    Ok(status)
+}
+
+/// `trap -p [signal ...]`: print commands that recreate the traps.
+fn print_traps(traps: &TrapTable, specs: &[String]) -> Result<i32> {
+    let mut out = io::stdout().lock();
+    let mut status = 0;
+    let selected: Vec<Trap> = if specs.is_empty() {
+        traps.actions.keys().copied().collect()
+    } else {
+        let mut v = Vec::new();
+        for spec in specs {
+            match Trap::parse(spec) {
+                Some(t) => v.push(t),
+                None => {
+                    eprintln!("trap: {spec}: invalid signal specification");
+                    status = 1;
+                }
+            }
+        }
+        v
+    };
+    for trap in selected {
+        if let Some(action) = traps.get(trap) {
+            writeln!(out, "trap -- {} {}", crate::shell::util::quote::single_quote(action), trap.name())?;
+        }
+    }
+    Ok(status)
+}
+
+/// `trap -l`: signal numbers and names, five per line.
+fn list_signals() -> Result<i32> {
+    let mut out = io::stdout().lock();
+    #[cfg(unix)]
+    {
+        let sigs: Vec<Signal> = Signal::iterator().collect();
+        for row in sigs.chunks(5) {
+            let cells: Vec<String> = row.iter().map(|s| format!("{:2}) {}", *s as i32, s.as_str())).collect();
+            writeln!(out, "{}", cells.join("\t"))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = &mut out;
+    Ok(0)
+}
@@ -0,0 +1,140 @@
+/// Shell pattern matching (`*`, `?`, `[...]`, `[!...]`, `\c`) over a whole string.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    match_from(&p, &t)
+}
+
+fn match_from(p: &[char], t: &[char]) -> bool {
+    let (mut pi, mut ti) = (0, 0);
+    // Backtrack point for the last `*`: (pattern index after it, text index).
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        let step = match p.get(pi) {
+            Some('*') => {
+                star = Some((pi + 1, ti));
+                pi += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match bracket(p, pi, t[ti]) {
+                Some((true, len)) => Some(len),
+                Some((false, _)) => None,
+                // No closing `]`: a literal `[`.
+                None => (t[ti] == '[').then_some(1),
+            },
+            Some('\\') if pi + 1 < p.len() => (p[pi + 1] == t[ti]).then_some(2),
+            Some(&c) => (c == t[ti]).then_some(1),
+            None => None,
+        };
+        match step {
+            Some(len) => {
+                pi += len;
+                ti += 1;
+            }
+            None => match star {
+                Some((sp, st)) => {
+                    pi = sp;
+                    ti = st + 1;
+                    star = Some((sp, st + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// Match `c` against the bracket expression at `p[start]`; returns whether it
+/// matched and the expression's length, or None if it is unterminated.
+fn bracket(p: &[char], start: usize, c: char) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negate = matches!(p.get(i), Some('!') | Some('^'));
+    if negate {
+        i += 1;
+    }
+    let mut hit = false;
+    let mut first = true;
+    while i < p.len() {
+        if p[i] == ']' && !first {
+            return Some((hit != negate, i + 1 - start));
+        }
+        first = false;
+        let lo = if p[i] == '\\' && i + 1 < p.len() { i += 1; p[i] } else { p[i] };
+        if p.get(i + 1) == Some(&'-') && p.get(i + 2).is_some_and(|&h| h != ']') {
+            let hi = p[i + 2];
+            hit |= lo <= c && c <= hi;
+            i += 3;
+        } else {
+            hit |= lo == c;
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Does `pattern` contain an unescaped glob character?
+pub fn has_magic(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => { chars.next(); }
+            '*' | '?' | '[' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Remove the backslashes that protect quoted characters in a pattern.
+pub fn unescape(pattern: &str) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Pathname expansion: the sorted paths matching `pattern`, empty if none.
+/// Names starting with `.` only match a pattern component that starts with
+/// `.` unless `dotglob` is set.
+pub fn expand_path(pattern: &str, dotglob: bool) -> Vec<String> {
+    let absolute = pattern.starts_with('/');
+    let mut paths = vec![if absolute { "/".to_string() } else { String::new() }];
+    for comp in pattern.split('/').filter(|c| !c.is_empty()) {
+        let mut next = Vec::new();
+        for base in &paths {
+            if !has_magic(comp) {
+                let p = format!("{base}{}", unescape(comp));
+                if std::fs::symlink_metadata(&p).is_ok() {
+                    next.push(p);
+                }
+                continue;
+            }
+            let dir = if base.is_empty() { "." } else { base.as_str() };
+            let Ok(entries) = std::fs::read_dir(dir) else { continue };
+            let mut names: Vec<String> = entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| e.file_name().into_string().ok())
+                .filter(|n| !n.starts_with('.') || comp.starts_with('.') || (dotglob && n != "." && n != ".."))
+                .filter(|n| matches(comp, n))
+                .collect();
+            names.sort();
+            next.extend(names.into_iter().map(|n| format!("{base}{n}")));
+        }
+        // Join the following component with a slash.
+        paths = next.into_iter().map(|p| if p.ends_with('/') { p } else { p + "/" }).collect();
+        if paths.is_empty() {
+            return Vec::new();
+        }
+    }
+    let trailing = pattern.ends_with('/');
+    paths
+        .into_iter()
+        .map(|p| if trailing || p == "/" { p } else { p.trim_end_matches('/').to_string() })
+        .collect()
+}
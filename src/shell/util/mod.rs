@@ -0,0 +1,5 @@
+pub mod path;
+pub mod print;
+pub mod time;
+pub mod quote;
+pub mod glob;
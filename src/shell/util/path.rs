@@ -2,6 +2,7 @@ use std::path::{PathBuf};
 use std::env;
 
 /// ~ expansion (bonus-ready)
+#[allow(dead_code)]
 pub fn expand_tilde(p: &str) -> PathBuf {
     if let Some(rest) = p.strip_prefix("~/") {
         if let Ok(home) = env::var("HOME") {
@@ -1,5 +1,6 @@
 use std::io::{self, Write};
 
+#[allow(dead_code)]
 pub fn eprintln_flush<S: AsRef<str>>(s: S) {
     let _ = writeln!(&mut io::stderr().lock(), "{}", s.as_ref());
 }
@@ -0,0 +1,72 @@
+/// Double-quote `s` so it survives re-parsing.
+pub fn double_quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') { out.push('\\'); }
+        out.push(c);
+    }
+    out.push('"');
+    out
+}
+
+/// Single-quote `s` (`'` becomes `'\''`) so it survives re-parsing.
+pub fn single_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// `s` as is if it reads back as one word, otherwise single-quoted.
+pub fn quote_if_needed(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || "_-./,:=+@%^".contains(c) || !c.is_ascii());
+    if plain { s.to_string() } else { single_quote(s) }
+}
+
+/// Backslash-quote `s` the way `printf %q` does; strings with control
+/// characters use `$'...'`.
+pub fn backslash_quote(s: &str) -> String {
+    if s.is_empty() {
+        return "''".into();
+    }
+    if s.chars().any(|c| c.is_control()) {
+        let mut out = String::from("$'");
+        for c in s.chars() {
+            match c {
+                '\n' => out.push_str("\\n"),
+                '\t' => out.push_str("\\t"),
+                '\r' => out.push_str("\\r"),
+                '\x1b' => out.push_str("\\E"),
+                '\'' | '\\' => { out.push('\\'); out.push(c); }
+                c if c.is_control() => out.push_str(&format!("\\{:03o}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('\'');
+        return out;
+    }
+    let mut out = String::with_capacity(s.len());
+    for (i, c) in s.chars().enumerate() {
+        let special = " '\"\\|&;()<>!{}*[]?^$`,".contains(c) || (i == 0 && matches!(c, '~' | '#'));
+        if special { out.push('\\'); }
+        out.push(c);
+    }
+    out
+}
+
+/// JSON string literal for `s`.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -1,11 +1,101 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Format an mtime-like string (placeholder)
+#[allow(dead_code)]
 pub fn format_mtime(_t: SystemTime) -> String {
     // Keep simple for now
     "-------- -- --:--".to_string()
 }
 
+#[allow(dead_code)]
 pub fn now_unix() -> u64 {
     SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
 }
+
+/// User and system CPU time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuTimes {
+    pub user: Duration,
+    pub sys: Duration,
+}
+
+impl CpuTimes {
+    #[cfg(unix)]
+    pub fn from_rusage(ru: &nix::libc::rusage) -> Self {
+        let tv = |t: nix::libc::timeval| Duration::new(t.tv_sec.max(0) as u64, (t.tv_usec.max(0) as u32) * 1000);
+        Self { user: tv(ru.ru_utime), sys: tv(ru.ru_stime) }
+    }
+
+    /// CPU time used by this process itself so far.
+    pub fn of_self() -> Self {
+        #[cfg(unix)]
+        {
+            use nix::sys::resource::{getrusage, UsageWho};
+            getrusage(UsageWho::RUSAGE_SELF).map(|u| Self::from_rusage(u.as_ref())).unwrap_or_default()
+        }
+        #[cfg(not(unix))]
+        Self::default()
+    }
+
+    pub fn add(&mut self, other: CpuTimes) {
+        self.user += other.user;
+        self.sys += other.sys;
+    }
+
+    /// Time used since `earlier` was taken.
+    pub fn since(&self, earlier: CpuTimes) -> CpuTimes {
+        CpuTimes { user: self.user.saturating_sub(earlier.user), sys: self.sys.saturating_sub(earlier.sys) }
+    }
+}
+
+/// bash's default `TIMEFORMAT`.
+pub const DEFAULT_TIMEFORMAT: &str = "\nreal\t%3lR\nuser\t%3lU\nsys\t%3lS";
+/// `time -p`.
+pub const POSIX_TIMEFORMAT: &str = "real %2R\nuser %2U\nsys %2S";
+
+/// Expand a `TIMEFORMAT` string: `%[p][l]R`, `U`, `S` (real, user and sys
+/// seconds with p decimals, `l` for `MmS.sss s`), `%P` (CPU percentage) and `%%`.
+pub fn format_times(format: &str, real: Duration, cpu: CpuTimes) -> String {
+    let mut out = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let prec = chars.next_if(|c| c.is_ascii_digit()).and_then(|d| d.to_digit(10)).map_or(3, |d| d.min(3) as usize);
+        let long = chars.next_if_eq(&'l').is_some();
+        let value = match chars.next() {
+            Some('R') => real,
+            Some('U') => cpu.user,
+            Some('S') => cpu.sys,
+            Some('P') => {
+                let total = (cpu.user + cpu.sys).as_secs_f64();
+                let pct = if real.is_zero() { 0.0 } else { total * 100.0 / real.as_secs_f64() };
+                out.push_str(&format!("{pct:.2}"));
+                continue;
+            }
+            Some('%') => {
+                out.push('%');
+                continue;
+            }
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+                continue;
+            }
+            None => {
+                out.push('%');
+                break;
+            }
+        };
+        let secs = value.as_secs_f64();
+        if long {
+            let mins = (secs / 60.0).floor();
+            out.push_str(&format!("{}m{:.prec$}s", mins as u64, secs - mins * 60.0));
+        } else {
+            out.push_str(&format!("{secs:.prec$}"));
+        }
+    }
+    out
+}
@@ -0,0 +1,254 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// A shell variable's value: a plain string, a sparse indexed array or an
+/// associative array.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Scalar(String),
+    Indexed(BTreeMap<usize, String>),
+    Assoc(BTreeMap<String, String>),
+}
+
+impl Value {
+    /// `$name` on an array means element 0 (indexed) or key "0" (assoc).
+    pub fn as_scalar(&self) -> Option<&str> {
+        match self {
+            Value::Scalar(s) => Some(s),
+            Value::Indexed(m) => m.get(&0).map(|s| s.as_str()),
+            Value::Assoc(m) => m.get("0").map(|s| s.as_str()),
+        }
+    }
+
+    /// Values in index/key order; a scalar is a one-element list.
+    pub fn values(&self) -> Vec<String> {
+        match self {
+            Value::Scalar(s) => vec![s.clone()],
+            Value::Indexed(m) => m.values().cloned().collect(),
+            Value::Assoc(m) => m.values().cloned().collect(),
+        }
+    }
+
+    /// Indices (`${!a[@]}`); a scalar has the single index 0.
+    pub fn keys(&self) -> Vec<String> {
+        match self {
+            Value::Scalar(_) => vec!["0".into()],
+            Value::Indexed(m) => m.keys().map(|k| k.to_string()).collect(),
+            Value::Assoc(m) => m.keys().cloned().collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Value::Scalar(_) => 1,
+            Value::Indexed(m) => m.len(),
+            Value::Assoc(m) => m.len(),
+        }
+    }
+
+    pub fn is_array(&self) -> bool {
+        !matches!(self, Value::Scalar(_))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Var {
+    pub value: Value,
+    pub exported: bool,
+}
+
+/// Shell variable table. The process environment is imported on startup;
+/// children only see exported scalars (arrays are never exported, as in bash).
+#[derive(Default)]
+pub struct VarStore {
+    map: HashMap<String, Var>,
+}
+
+impl VarStore {
+    pub fn from_env() -> Self {
+        Self::from_pairs(std::env::vars())
+    }
+
+    /// Exported scalars from `NAME=value` pairs, as if they were the environment.
+    pub fn from_pairs(pairs: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut store = Self::default();
+        for (k, v) in pairs {
+            store.map.insert(k, Var { value: Value::Scalar(v), exported: true });
+        }
+        store
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Var> {
+        self.map.get(name)
+    }
+
+    /// Scalar view of a variable (element 0 for arrays).
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.map.get(name).and_then(|v| v.value.as_scalar())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.map.contains_key(name)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.map.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// `name=value`. On an array this sets element 0 / key "0".
+    pub fn set_scalar(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.map.get_mut(name) {
+            Some(Var { value: Value::Indexed(m), .. }) => { m.insert(0, value); }
+            Some(Var { value: Value::Assoc(m), .. }) => { m.insert("0".into(), value); }
+            Some(var) => var.value = Value::Scalar(value),
+            None => {
+                self.map.insert(name.to_string(), Var { value: Value::Scalar(value), exported: false });
+            }
+        }
+    }
+
+    /// Replace the whole value, keeping the export flag.
+    pub fn set_value(&mut self, name: &str, value: Value) {
+        match self.map.get_mut(name) {
+            Some(var) => var.value = value,
+            None => {
+                self.map.insert(name.to_string(), Var { value, exported: false });
+            }
+        }
+    }
+
+    /// `name[key]=value`. Creates an indexed array unless `name` is associative.
+    pub fn set_element(&mut self, name: &str, key: &str, value: String) -> Result<(), String> {
+        let var = self.map.entry(name.to_string()).or_insert_with(|| Var {
+            value: Value::Indexed(BTreeMap::new()),
+            exported: false,
+        });
+        if let Value::Scalar(s) = &var.value {
+            let mut m = BTreeMap::new();
+            m.insert(0, s.clone());
+            var.value = Value::Indexed(m);
+        }
+        match &mut var.value {
+            Value::Assoc(m) => { m.insert(key.to_string(), value); }
+            Value::Indexed(m) => {
+                let idx = resolve_index(m, key)?;
+                m.insert(idx, value);
+            }
+            Value::Scalar(_) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Look up `name[key]`.
+    pub fn get_element(&self, name: &str, key: &str) -> Option<String> {
+        match &self.map.get(name)?.value {
+            Value::Scalar(s) => match key.trim().parse::<i64>() {
+                Ok(0) | Ok(-1) => Some(s.clone()),
+                _ => None,
+            },
+            Value::Indexed(m) => resolve_index(m, key).ok().and_then(|i| m.get(&i).cloned()),
+            Value::Assoc(m) => m.get(key).cloned(),
+        }
+    }
+
+    /// `declare -a` / `declare -A`: convert or create with the given kind.
+    pub fn declare_array(&mut self, name: &str, assoc: bool) -> Result<(), String> {
+        let var = self.map.entry(name.to_string()).or_insert_with(|| Var {
+            value: if assoc { Value::Assoc(BTreeMap::new()) } else { Value::Indexed(BTreeMap::new()) },
+            exported: false,
+        });
+        match (&var.value, assoc) {
+            (Value::Scalar(s), false) => {
+                let mut m = BTreeMap::new();
+                m.insert(0, s.clone());
+                var.value = Value::Indexed(m);
+            }
+            (Value::Scalar(s), true) => {
+                let mut m = BTreeMap::new();
+                m.insert("0".to_string(), s.clone());
+                var.value = Value::Assoc(m);
+            }
+            (Value::Indexed(_), true) => {
+                return Err(format!("{name}: cannot convert indexed to associative array"));
+            }
+            (Value::Assoc(_), false) => {
+                return Err(format!("{name}: cannot convert associative to indexed array"));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn unset(&mut self, name: &str) {
+        self.map.remove(name);
+    }
+
+    /// Put back a variable saved with `get(..).cloned()` (temporary assignments).
+    pub fn restore(&mut self, name: &str, saved: Option<Var>) {
+        match saved {
+            Some(var) => { self.map.insert(name.to_string(), var); }
+            None => { self.map.remove(name); }
+        }
+    }
+
+    /// `unset name[key]`.
+    pub fn unset_element(&mut self, name: &str, key: &str) {
+        match self.map.get_mut(name).map(|v| &mut v.value) {
+            Some(Value::Indexed(m)) => {
+                if let Ok(i) = resolve_index(m, key) { m.remove(&i); }
+            }
+            Some(Value::Assoc(m)) => { m.remove(key); }
+            Some(Value::Scalar(_)) => {
+                if matches!(key.trim().parse::<i64>(), Ok(0) | Ok(-1)) { self.map.remove(name); }
+            }
+            None => {}
+        }
+    }
+
+    pub fn set_exported(&mut self, name: &str, exported: bool) {
+        match self.map.get_mut(name) {
+            Some(var) => var.exported = exported,
+            None if exported => {
+                // `export NAME` before assignment: remember the flag on an empty value.
+                self.map.insert(name.to_string(), Var { value: Value::Scalar(String::new()), exported });
+            }
+            None => {}
+        }
+    }
+
+    /// `NAME=value` pairs handed to children: exported scalars only.
+    #[cfg(feature = "external")]
+    pub fn exported_env(&self) -> Vec<(String, String)> {
+        let mut env: Vec<(String, String)> = self.map.iter()
+            .filter_map(|(k, v)| match (&v.value, v.exported) {
+                (Value::Scalar(s), true) => Some((k.clone(), s.clone())),
+                _ => None,
+            })
+            .collect();
+        env.sort();
+        env
+    }
+}
+
+/// Turn a subscript into an index; negative values count back from the end.
+fn resolve_index(m: &BTreeMap<usize, String>, key: &str) -> Result<usize, String> {
+    let n: i64 = key.trim().parse().map_err(|_| format!("{key}: bad array subscript"))?;
+    if n >= 0 {
+        return Ok(n as usize);
+    }
+    let top = m.keys().next_back().map(|k| *k as i64 + 1).unwrap_or(0);
+    let idx = top + n;
+    if idx < 0 {
+        return Err(format!("{key}: bad array subscript"));
+    }
+    Ok(idx as usize)
+}
+
+/// `NAME` must be a valid identifier.
+pub fn is_valid_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}